@@ -1,9 +1,106 @@
+use mlua::Lua;
+use mlua::prelude::{LuaResult, LuaTable};
+use serde_json::{json, Value};
 use crate::fitness_function_calculator::{FitnessFunctionCalculator, FitnessFunctionCalculatorStats};
 use crate::target::Target;
 
+pub const MANA_COST_AUTO_TARGET_NAME: &str = "manaCost";
+pub const MANA_REGEN_AUTO_TARGET_NAME: &str = "manaRegen";
+
+// Name, requirement stat and stat which should reach it
+const ATTRIBUTE_AUTO_TARGETS: [(&str, &str, &str); 3] = [
+    ("strength", "ReqStr", "Str"),
+    ("intelligence", "ReqInt", "Int"),
+    ("dexterity", "ReqDex", "Dex")
+];
+
+// Registry of auto targets with their descriptions, StartSolve adds every enabled one after user targets
+pub const AUTO_TARGETS: [(&str, &str); 5] = [
+    (MANA_COST_AUTO_TARGET_NAME, "Unreserved mana covers mana cost of skill"),
+    (MANA_REGEN_AUTO_TARGET_NAME, "Mana regeneration and leech cover mana spent per second"),
+    ("strength", "Strength reaches requirement of items and gems"),
+    ("intelligence", "Intelligence reaches requirement of items and gems"),
+    ("dexterity", "Dexterity reaches requirement of items and gems")
+];
+
+// Settings of auto target from registry, StartSolve option autoTargets changes them
+#[derive(Clone, Debug)]
+pub struct AutoTargetSettings
+{
+    pub name: &'static str,
+    pub is_enabled: bool,
+    pub weight: f64,
+    pub is_constraint: bool
+}
+
+pub fn get_default_auto_target_settings(is_constraint: bool) -> Vec<AutoTargetSettings>
+{
+    AUTO_TARGETS
+        .iter()
+        .map(|(name, _)| AutoTargetSettings {
+            name,
+            is_enabled: true,
+            weight: 1.0,
+            is_constraint
+        })
+        .collect()
+}
+
+pub fn create_auto_target(settings: &AutoTargetSettings) -> Result<Box<dyn Target>, String>
+{
+    match settings.name {
+        MANA_COST_AUTO_TARGET_NAME => Ok(Box::new(AutoTargetManaCost {
+            weight: settings.weight,
+            is_constraint: settings.is_constraint
+        })),
+        MANA_REGEN_AUTO_TARGET_NAME => Ok(Box::new(AutoTargetManaRegen {
+            weight: settings.weight,
+            is_constraint: settings.is_constraint
+        })),
+        name => {
+            let (_, target_stat_name, current_stat_name) = ATTRIBUTE_AUTO_TARGETS
+                .iter()
+                .find(|(attribute_name, _, _)| *attribute_name == name)
+                .ok_or_else(|| format!("Unknown auto target {}", name))?;
+
+            Ok(Box::new(AutoTargetFromStatToStat {
+                name: name.to_string(),
+                target_stat_name: target_stat_name.to_string(),
+                current_stat_name: current_stat_name.to_string(),
+                weight: settings.weight,
+                is_constraint: settings.is_constraint
+            }))
+        }
+    }
+}
+
+// Registry with default settings, for Lua
+pub fn lua_get_auto_targets<'lua>(lua: &'lua Lua, (): ()) -> LuaResult<LuaTable<'lua>>
+{
+    let auto_targets_table = lua.create_table()?;
+
+    for (index, settings) in get_default_auto_target_settings(false).iter().enumerate()
+    {
+        let auto_target_table = lua.create_table()?;
+
+        auto_target_table.set("name", settings.name)?;
+        auto_target_table.set("description", AUTO_TARGETS[index].1)?;
+        auto_target_table.set("enabled", settings.is_enabled)?;
+        auto_target_table.set("weight", settings.weight)?;
+        auto_target_table.set("constraint", settings.is_constraint)?;
+
+        auto_targets_table.set(index + 1, auto_target_table)?;
+    }
+
+    Ok(auto_targets_table)
+}
+
 #[derive(Clone)]
 pub struct AutoTargetManaRegen
-{}
+{
+    pub(crate) weight: f64,
+    pub(crate) is_constraint: bool
+}
 
 impl Target for AutoTargetManaRegen
 {
@@ -11,57 +108,84 @@ impl Target for AutoTargetManaRegen
         Box::new(self.clone())
     }
 
-    fn calc_fitness_score(&self, fitness_function_calculator: &FitnessFunctionCalculator, stats: &mut FitnessFunctionCalculatorStats) -> f64 {
+    fn calc_fitness_score(&self, fitness_function_calculator: &FitnessFunctionCalculator, stats: &mut FitnessFunctionCalculatorStats) -> LuaResult<f64> {
         let mut mana_recovery_sum = 0.0;
 
-        match stats.try_get_stat(String::from("player"), String::from("ManaRegenRecovery")) {
+        match stats.try_get_stat(String::from("player"), String::from("ManaRegenRecovery"))? {
             None => {},
             Some(mana_regen_recovery) => {
                 mana_recovery_sum += mana_regen_recovery;
             }
         }
 
-        match stats.try_get_stat(String::from("player"), String::from("ManaLeechGainRate")) {
+        match stats.try_get_stat(String::from("player"), String::from("ManaLeechGainRate"))? {
             None => {},
             Some(mana_leech_gain_rate) => {
                 mana_recovery_sum += mana_leech_gain_rate;
             }
         }
 
-        match stats.try_get_stat(String::from("player"), String::from("ManaPerSecondCost")) {
+        match stats.try_get_stat(String::from("player"), String::from("ManaPerSecondCost"))? {
             None => {
-                1.0
+                Ok(1.0)
             },
             Some(mana_per_second_cost) => {
-                fitness_function_calculator.calc_target_mul(mana_recovery_sum, 1.0, mana_per_second_cost, false)
+                Ok(fitness_function_calculator.calc_target_mul(mana_recovery_sum, mana_per_second_cost, false))
             }
         }
     }
 
-    fn get_maximize_value(&self, stats: &mut FitnessFunctionCalculatorStats) -> f64 {
+    fn get_maximize_value(&self, stats: &mut FitnessFunctionCalculatorStats) -> LuaResult<f64> {
         let mut mana_recovery_sum = 0.0;
 
-        match stats.try_get_stat(String::from("player"), String::from("ManaRegenRecovery")) {
+        match stats.try_get_stat(String::from("player"), String::from("ManaRegenRecovery"))? {
             None => {},
             Some(mana_regen_recovery) => {
                 mana_recovery_sum += mana_regen_recovery;
             }
         }
 
-        match stats.try_get_stat(String::from("player"), String::from("ManaLeechGainRate")) {
+        match stats.try_get_stat(String::from("player"), String::from("ManaLeechGainRate"))? {
             None => {},
             Some(mana_leech_gain_rate) => {
                 mana_recovery_sum += mana_leech_gain_rate;
             }
         }
 
-        mana_recovery_sum
+        Ok(mana_recovery_sum)
+    }
+
+    fn get_weight(&self) -> f64 {
+        self.weight
+    }
+
+    fn is_user_requirement(&self) -> bool {
+        false
+    }
+
+    fn is_constraint(&self) -> bool {
+        self.is_constraint
+    }
+
+    fn get_auto_target_name(&self) -> Option<&str> {
+        Some(MANA_REGEN_AUTO_TARGET_NAME)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "type": "autoManaRegen",
+            "weight": self.weight,
+            "isConstraint": self.is_constraint
+        })
     }
 }
 
 #[derive(Clone)]
 pub struct AutoTargetManaCost
-{}
+{
+    pub(crate) weight: f64,
+    pub(crate) is_constraint: bool
+}
 
 impl Target for AutoTargetManaCost
 {
@@ -69,41 +193,68 @@ impl Target for AutoTargetManaCost
         Box::new(self.clone())
     }
 
-    fn calc_fitness_score(&self, fitness_function_calculator: &FitnessFunctionCalculator, stats: &mut FitnessFunctionCalculatorStats) -> f64 {
-        match stats.try_get_stat(String::from("player"), String::from("ManaUnreserved")) {
+    fn calc_fitness_score(&self, fitness_function_calculator: &FitnessFunctionCalculator, stats: &mut FitnessFunctionCalculatorStats) -> LuaResult<f64> {
+        match stats.try_get_stat(String::from("player"), String::from("ManaUnreserved"))? {
             None => {
-                0.01
+                Ok(0.01)
             },
             Some(unreserved_mana) => {
-                match stats.try_get_stat(String::from("player"), String::from("ManaCost")) {
+                match stats.try_get_stat(String::from("player"), String::from("ManaCost"))? {
                     None => {
-                        0.01
+                        Ok(0.01)
                     },
                     Some(mana_cost) => {
-                        fitness_function_calculator.calc_target_mul(unreserved_mana, 1.0, mana_cost, false)
+                        Ok(fitness_function_calculator.calc_target_mul(unreserved_mana, mana_cost, false))
                     }
                 }
             }
         }
     }
 
-    fn get_maximize_value(&self, stats: &mut FitnessFunctionCalculatorStats) -> f64 {
-        match stats.try_get_stat(String::from("player"), String::from("ManaUnreserved")) {
+    fn get_maximize_value(&self, stats: &mut FitnessFunctionCalculatorStats) -> LuaResult<f64> {
+        match stats.try_get_stat(String::from("player"), String::from("ManaUnreserved"))? {
             None => {
-                0.0
+                Ok(0.0)
             },
             Some(unreserved_mana) => {
-                unreserved_mana
+                Ok(unreserved_mana)
             }
         }
     }
+
+    fn get_weight(&self) -> f64 {
+        self.weight
+    }
+
+    fn is_user_requirement(&self) -> bool {
+        false
+    }
+
+    fn is_constraint(&self) -> bool {
+        self.is_constraint
+    }
+
+    fn get_auto_target_name(&self) -> Option<&str> {
+        Some(MANA_COST_AUTO_TARGET_NAME)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "type": "autoManaCost",
+            "weight": self.weight,
+            "isConstraint": self.is_constraint
+        })
+    }
 }
 
 #[derive(Clone)]
 pub struct AutoTargetFromStatToStat
 {
+    pub(crate) name: String,
     pub(crate) target_stat_name: String,
-    pub(crate) current_stat_name: String
+    pub(crate) current_stat_name: String,
+    pub(crate) weight: f64,
+    pub(crate) is_constraint: bool
 }
 
 impl Target for AutoTargetFromStatToStat
@@ -112,39 +263,92 @@ impl Target for AutoTargetFromStatToStat
         Box::new(self.clone())
     }
 
-    fn calc_fitness_score(&self, fitness_function_calculator: &FitnessFunctionCalculator, stats: &mut FitnessFunctionCalculatorStats) -> f64 {
-        match stats.try_get_stat(String::from("player"),  self.target_stat_name.clone()) {
+    fn calc_fitness_score(&self, fitness_function_calculator: &FitnessFunctionCalculator, stats: &mut FitnessFunctionCalculatorStats) -> LuaResult<f64> {
+        match stats.try_get_stat(String::from("player"),  self.target_stat_name.clone())? {
             None => {
-                1.0
+                Ok(1.0)
             },
             Some(req) => {
                 if req != 0.0
                 {
-                    match stats.try_get_stat(String::from("player"),  self.current_stat_name.clone()) {
+                    match stats.try_get_stat(String::from("player"),  self.current_stat_name.clone())? {
                         None => {
-                            0.01
+                            Ok(0.01)
                         },
                         Some(stat) => {
-                            fitness_function_calculator.calc_target_mul(stat, 1.0, req, false)
+                            Ok(fitness_function_calculator.calc_target_mul(stat, req, false))
                         }
                     }
                 }
                 else
                 {
-                    1.0
+                    Ok(1.0)
                 }
             }
         }
     }
 
-    fn get_maximize_value(&self, stats: &mut FitnessFunctionCalculatorStats) -> f64 {
-        match stats.try_get_stat(String::from("player"),  self.current_stat_name.clone()) {
+    fn get_maximize_value(&self, stats: &mut FitnessFunctionCalculatorStats) -> LuaResult<f64> {
+        match stats.try_get_stat(String::from("player"),  self.current_stat_name.clone())? {
             None => {
-                0.0
+                Ok(0.0)
             },
             Some(stat) => {
-                stat
+                Ok(stat)
             }
         }
     }
+
+    fn get_weight(&self) -> f64 {
+        self.weight
+    }
+
+    fn is_user_requirement(&self) -> bool {
+        false
+    }
+
+    fn is_constraint(&self) -> bool {
+        self.is_constraint
+    }
+
+    fn get_auto_target_name(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "type": "autoFromStatToStat",
+            "name": self.name,
+            "targetStatName": self.target_stat_name,
+            "currentStatName": self.current_stat_name,
+            "weight": self.weight,
+            "isConstraint": self.is_constraint
+        })
+    }
+}
+
+impl AutoTargetFromStatToStat
+{
+    pub fn from_json(value: &Value) -> Result<Self, String>
+    {
+        let target_stat_name = value["targetStatName"].as_str().ok_or("targetStatName is not found")?.to_string();
+
+        // Checkpoints of older versions have no names, they had attribute targets only
+        let name =
+            match value["name"].as_str() {
+                Some(name) => name.to_string(),
+                None => ATTRIBUTE_AUTO_TARGETS
+                    .iter()
+                    .find(|(_, attribute_target_stat_name, _)| *attribute_target_stat_name == target_stat_name)
+                    .map_or_else(|| target_stat_name.clone(), |(name, _, _)| name.to_string())
+            };
+
+        Ok(AutoTargetFromStatToStat {
+            name,
+            target_stat_name,
+            current_stat_name: value["currentStatName"].as_str().ok_or("currentStatName is not found")?.to_string(),
+            weight: value["weight"].as_f64().unwrap_or(1.0),
+            is_constraint: value["isConstraint"].as_bool().unwrap_or(false)
+        })
+    }
 }
@@ -0,0 +1,400 @@
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use rand::prelude::StdRng;
+use rand::SeedableRng;
+use serde_json::{json, Value};
+use crate::repair::RepairPolicy;
+use crate::dna::{mastery_bits_to_choices, validate_mastery_choices, CrossoverMode, DnaContext, DnaData, MutationMode, MASTERY_CHOICES_COUNT};
+use crate::pob_solver::SolveParameters;
+use crate::stat_cache::StatCacheSettings;
+use crate::stop_criteria::StopCriteria;
+use crate::solve_options::{DEFAULT_CROSSOVER_ODDS, DEFAULT_ELIMINATE_DUPLICATES, DEFAULT_ELITES_COUNT, DEFAULT_LOCAL_SEARCH_MAX_STEPS, DEFAULT_MAX_MUTATE_CLUSTER_SIZE, DEFAULT_MIGRATION_INTERVAL, DEFAULT_MIGRATION_RATE, DEFAULT_MUTATION_ODDS, DEFAULT_STAT_CACHE_MAX_ENTRIES, DEFAULT_TOURNAMENT_SIZE, OptimizerType};
+use crate::islands::PopulationParameters;
+use crate::constraints::get_constraint_target_indexes;
+use crate::fitness_function_calculator::FitnessAggregation;
+use crate::tree_graph::TreeGraph;
+use crate::target::{create_target_from_json, Target};
+
+const CHECKPOINT_VERSION: u64 = 2;
+// Dnas of first version keep a bit for each mastery effect
+const CHECKPOINT_BITS_VERSION: u64 = 1;
+
+pub struct Checkpoint
+{
+    pub generation_number: usize,
+    pub solve_parameters: SolveParameters,
+    pub dna_context: Arc<DnaContext>,
+    pub targets: Vec<Box<dyn Target>>,
+    pub population: Vec<DnaData>
+}
+
+impl Checkpoint
+{
+    pub fn save(&self, path: &str) -> Result<(), String>
+    {
+        let content = serde_json::to_string(&self.to_json()).map_err(|err| err.to_string())?;
+
+        fs::write(path, content).map_err(|err| format!("Cannot write checkpoint {}: {}", path, err))
+    }
+
+    pub fn load(path: &str) -> Result<Checkpoint, String>
+    {
+        let content = fs::read_to_string(path).map_err(|err| format!("Cannot read checkpoint {}: {}", path, err))?;
+
+        let value: Value = serde_json::from_str(&content).map_err(|err| format!("Checkpoint {} is corrupted: {}", path, err))?;
+
+        Checkpoint::from_json(&value)
+    }
+
+    fn to_json(&self) -> Value
+    {
+        json!({
+            "version": CHECKPOINT_VERSION,
+            "generationNumber": self.generation_number,
+            "stopGenerationsEps": self.solve_parameters.stop_generations_eps,
+            "populationMaxGenerationSize": self.solve_parameters.population_max_generation_size,
+            "treeNodesCount": self.solve_parameters.tree_nodes_count,
+            "masteriesNodesCount": self.solve_parameters.masteries_nodes_count,
+            "treeVersion": self.solve_parameters.tree_version,
+            "targetNormalNodesCount": self.solve_parameters.target_normal_nodes_count,
+            "targetAscendancyNodesCount": self.solve_parameters.target_ascendancy_nodes_count,
+            "mutationOdds": self.solve_parameters.mutation_odds,
+            "crossoverOdds": self.solve_parameters.crossover_odds,
+            "seed": self.solve_parameters.seed,
+            "timeLimitSeconds": self.solve_parameters.stop_criteria.time_limit.map(|time_limit| time_limit.as_secs_f64()),
+            "maxEvaluations": self.solve_parameters.stop_criteria.max_evaluations,
+            "maxGenerations": self.solve_parameters.stop_criteria.max_generations,
+            "stopWhenTargetsMet": self.solve_parameters.stop_criteria.is_stop_when_targets_met,
+            "statCacheDirectory": self.solve_parameters.stat_cache_settings.as_ref().map(|settings| settings.directory.clone()),
+            "statCacheBuildKey": self.solve_parameters.stat_cache_settings.as_ref().map(|settings| settings.build_key.clone()),
+            "statCacheTreeVersion": self.solve_parameters.stat_cache_settings.as_ref().map(|settings| settings.tree_version.clone()),
+            "statCacheMaxEntries": self.solve_parameters.stat_cache_settings.as_ref().map(|settings| settings.max_entries_count),
+            "optimizer": self.solve_parameters.optimizer_type.name(),
+            "elitesCount": self.solve_parameters.elites_count,
+            "tournamentSize": self.solve_parameters.tournament_size,
+            "localSearch": self.solve_parameters.is_local_search_after_solve,
+            "localSearchInterval": self.solve_parameters.local_search_interval,
+            "localSearchMaxSteps": self.solve_parameters.local_search_max_steps,
+            "eliminateDuplicates": self.solve_parameters.is_eliminate_duplicates,
+            "islands": self.solve_parameters.islands.iter().map(population_parameters_to_json).collect::<Vec<Value>>(),
+            "migrationInterval": self.solve_parameters.migration_interval,
+            "migrationRate": self.solve_parameters.migration_rate,
+            "fitnessAggregation": self.solve_parameters.fitness_aggregation.name(),
+            "treeGraph": self.dna_context.tree_graph.as_ref().map(|tree_graph| tree_graph.to_json()),
+            "mutationClusterSize": self.dna_context.max_mutate_cluster_size,
+            "mutationMode": self.dna_context.mutation_mode.name(),
+            "crossoverMode": self.dna_context.crossover_mode.name(),
+            "repairPolicy": self.dna_context.repair_policy.name(),
+            "lockedNodeIndexes": self.dna_context.locked_node_indexes,
+            "forbiddenNodeIndexes": self.dna_context.forbidden_node_indexes,
+            "targets": self.targets.iter().map(|target| target.to_json()).collect::<Vec<Value>>(),
+            "population": self.population.iter().map(dna_data_to_json).collect::<Vec<Value>>()
+        })
+    }
+
+    fn from_json(value: &Value) -> Result<Checkpoint, String>
+    {
+        let version = get_usize(value, "version")? as u64;
+
+        if version != CHECKPOINT_VERSION && version != CHECKPOINT_BITS_VERSION
+        {
+            return Err(format!("Unsupported checkpoint version: {}", version));
+        }
+
+        let solve_parameters = SolveParameters {
+            stop_generations_eps: get_usize(value, "stopGenerationsEps")?,
+            population_max_generation_size: get_usize(value, "populationMaxGenerationSize")?,
+            tree_nodes_count: get_usize(value, "treeNodesCount")?,
+            masteries_nodes_count: get_usize(value, "masteriesNodesCount")?,
+            tree_version: value["treeVersion"].as_str().map(String::from),
+            target_normal_nodes_count: get_usize(value, "targetNormalNodesCount")?,
+            target_ascendancy_nodes_count: get_usize(value, "targetAscendancyNodesCount")?,
+            mutation_odds: value["mutationOdds"].as_f64().unwrap_or(DEFAULT_MUTATION_ODDS),
+            crossover_odds: value["crossoverOdds"].as_f64().unwrap_or(DEFAULT_CROSSOVER_ODDS),
+            seed: value["seed"].as_u64().unwrap_or_default(),
+            stop_criteria: StopCriteria {
+                time_limit: value["timeLimitSeconds"].as_f64().map(Duration::from_secs_f64),
+                max_evaluations: value["maxEvaluations"].as_u64().map(|max_evaluations| max_evaluations as usize),
+                max_generations: value["maxGenerations"].as_u64().map(|max_generations| max_generations as usize),
+                is_stop_when_targets_met: value["stopWhenTargetsMet"].as_bool().unwrap_or(false)
+            },
+            stat_cache_settings: match (value["statCacheDirectory"].as_str(), value["statCacheBuildKey"].as_str(), value["statCacheTreeVersion"].as_str()) {
+                (Some(directory), Some(build_key), Some(tree_version)) => Some(StatCacheSettings {
+                    directory: directory.to_string(),
+                    build_key: build_key.to_string(),
+                    tree_version: tree_version.to_string(),
+                    max_entries_count: get_usize(value, "statCacheMaxEntries").unwrap_or(DEFAULT_STAT_CACHE_MAX_ENTRIES)
+                }),
+                _ => None
+            },
+            optimizer_type: match value["optimizer"].as_str() {
+                None => OptimizerType::Nsga2,
+                Some(optimizer_name) => OptimizerType::from_name(optimizer_name).ok_or(format!("Unknown optimizer: {}", optimizer_name))?
+            },
+            elites_count: get_usize(value, "elitesCount").unwrap_or(DEFAULT_ELITES_COUNT),
+            tournament_size: get_usize(value, "tournamentSize").unwrap_or(DEFAULT_TOURNAMENT_SIZE),
+            is_local_search_after_solve: value["localSearch"].as_bool().unwrap_or(false),
+            local_search_interval: value["localSearchInterval"].as_u64().map(|local_search_interval| local_search_interval as usize),
+            local_search_max_steps: get_usize(value, "localSearchMaxSteps").unwrap_or(DEFAULT_LOCAL_SEARCH_MAX_STEPS),
+            is_eliminate_duplicates: value["eliminateDuplicates"].as_bool().unwrap_or(DEFAULT_ELIMINATE_DUPLICATES),
+            islands: match value["islands"].as_array() {
+                None => vec![],
+                Some(island_values) => island_values
+                    .iter()
+                    .map(population_parameters_from_json)
+                    .collect::<Result<Vec<PopulationParameters>, String>>()?
+            },
+            migration_interval: get_usize(value, "migrationInterval").unwrap_or(DEFAULT_MIGRATION_INTERVAL),
+            migration_rate: get_usize(value, "migrationRate").unwrap_or(DEFAULT_MIGRATION_RATE),
+            fitness_aggregation: match value["fitnessAggregation"].as_str() {
+                None => FitnessAggregation::Product,
+                Some(fitness_aggregation_name) => FitnessAggregation::from_name(fitness_aggregation_name).ok_or(format!("Unknown fitness aggregation: {}", fitness_aggregation_name))?
+            }
+        };
+
+        let generation_number = get_usize(value, "generationNumber")?;
+
+        let mut targets = Vec::new();
+        for target_value in get_array(value, "targets")?
+        {
+            targets.push(create_target_from_json(target_value)?);
+        }
+
+        let dna_context = DnaContext {
+            locked_node_indexes: get_indexes(value, "lockedNodeIndexes", solve_parameters.tree_nodes_count)?,
+            forbidden_node_indexes: get_indexes(value, "forbiddenNodeIndexes", solve_parameters.tree_nodes_count)?,
+            max_mutate_cluster_size: get_usize(value, "mutationClusterSize").unwrap_or(DEFAULT_MAX_MUTATE_CLUSTER_SIZE),
+            mutation_mode: match value["mutationMode"].as_str() {
+                None => MutationMode::Cluster,
+                Some(mutation_mode_name) => MutationMode::from_name(mutation_mode_name).ok_or(format!("Unknown mutation mode: {}", mutation_mode_name))?
+            },
+            // Checkpoints of older versions were made with range crossover
+            crossover_mode: match value["crossoverMode"].as_str() {
+                None => CrossoverMode::Range,
+                Some(crossover_mode_name) => CrossoverMode::from_name(crossover_mode_name).ok_or(format!("Unknown crossover mode: {}", crossover_mode_name))?
+            },
+            repair_policy: match value["repairPolicy"].as_str() {
+                None => RepairPolicy::Random,
+                Some(repair_policy_name) => RepairPolicy::from_name(repair_policy_name).ok_or(format!("Unknown repair policy: {}", repair_policy_name))?
+            },
+            constraint_target_indexes: get_constraint_target_indexes(&targets),
+            tree_graph: match &value["treeGraph"] {
+                Value::Null => None,
+                tree_graph_value => Some(Arc::new(TreeGraph::from_json(tree_graph_value, solve_parameters.tree_nodes_count)?))
+            },
+            // Resumed run is repeatable too, but it does not continue random sequence of the saved one
+            rng: Mutex::new(StdRng::seed_from_u64(solve_parameters.seed.wrapping_add(generation_number as u64)))
+        };
+
+        let mut population = Vec::new();
+        for dna_value in get_array(value, "population")?
+        {
+            let dna_data = dna_data_from_json(dna_value, version)?;
+
+            check_dna_data_sizes(&dna_data, solve_parameters.tree_nodes_count, solve_parameters.masteries_nodes_count, targets.len())?;
+
+            population.push(dna_data);
+        }
+
+        // Population is split between islands by their sizes on resume
+        if !solve_parameters.islands.is_empty()
+            && population.len() != solve_parameters.islands.iter().map(|island| island.population_size).sum::<usize>()
+        {
+            return Err(String::from("Checkpoint population does not match its islands"));
+        }
+
+        Ok(Checkpoint {
+            generation_number,
+            solve_parameters,
+            dna_context: Arc::new(dna_context),
+            targets,
+            population
+        })
+    }
+}
+
+fn population_parameters_to_json(population_parameters: &PopulationParameters) -> Value
+{
+    json!({
+        "optimizer": population_parameters.optimizer_type.name(),
+        "populationSize": population_parameters.population_size,
+        "mutationOdds": population_parameters.mutation_odds,
+        "crossoverOdds": population_parameters.crossover_odds,
+        "elitesCount": population_parameters.elites_count,
+        "tournamentSize": population_parameters.tournament_size
+    })
+}
+
+fn population_parameters_from_json(value: &Value) -> Result<PopulationParameters, String>
+{
+    let optimizer_name = value["optimizer"].as_str().ok_or("Checkpoint island has no optimizer")?;
+
+    Ok(PopulationParameters {
+        optimizer_type: OptimizerType::from_name(optimizer_name).ok_or(format!("Unknown optimizer: {}", optimizer_name))?,
+        population_size: get_usize(value, "populationSize")?,
+        mutation_odds: value["mutationOdds"].as_f64().ok_or("Checkpoint island has no mutationOdds")?,
+        crossover_odds: value["crossoverOdds"].as_f64().ok_or("Checkpoint island has no crossoverOdds")?,
+        elites_count: get_usize(value, "elitesCount")?,
+        tournament_size: get_usize(value, "tournamentSize")?
+    })
+}
+
+fn check_dna_data_sizes(dna_data: &DnaData, tree_nodes_count: usize, masteries_count: usize, targets_count: usize) -> Result<(), String>
+{
+    if dna_data.body_nodes.len() != tree_nodes_count
+        || dna_data.body_masteries.len() != masteries_count * MASTERY_CHOICES_COUNT
+        || dna_data.fitness_score_targets.len() != targets_count
+    {
+        return Err(String::from("Checkpoint population does not match its parameters"));
+    }
+
+    Ok(())
+}
+
+fn dna_data_to_json(dna_data: &DnaData) -> Value
+{
+    json!({
+        "nodes": bits_to_string(&dna_data.body_nodes),
+        "masteryChoices": dna_data.body_masteries,
+        "maxCountNodes": dna_data.max_count_nodes,
+        "fitnessScore": dna_data.fitness_score,
+        "fitnessScoreTargets": dna_data.fitness_score_targets
+    })
+}
+
+fn dna_data_from_json(value: &Value, version: u64) -> Result<DnaData, String>
+{
+    let body_masteries =
+        if version == CHECKPOINT_BITS_VERSION
+        {
+            mastery_bits_to_choices(&bits_from_string(value["masteries"].as_str().ok_or("masteries is not found")?)?)?
+        }
+        else
+        {
+            let mastery_choices = get_array(value, "masteryChoices")?
+                .iter()
+                .map(|choice| choice.as_u64().filter(|choice| *choice <= u8::MAX as u64).map(|choice| choice as u8).ok_or(String::from("Invalid mastery choice")))
+                .collect::<Result<Vec<u8>, String>>()?;
+
+            validate_mastery_choices(&mastery_choices)?;
+
+            mastery_choices
+        };
+
+    let fitness_score_targets = get_array(value, "fitnessScoreTargets")?
+        .iter()
+        .map(|score| score.as_f64().unwrap_or(-1.0))
+        .collect();
+
+    Ok(DnaData {
+        body_nodes: bits_from_string(value["nodes"].as_str().ok_or("nodes is not found")?)?,
+        body_masteries,
+        max_count_nodes: get_usize(value, "maxCountNodes")?,
+        fitness_score: value["fitnessScore"].as_f64().unwrap_or(-1.0),
+        fitness_score_targets,
+        unreachable_node_indexes: vec![],
+        decoded_tree_key: None,
+        allocated_node_ids: vec![]
+    })
+}
+
+fn bits_to_string(bits: &[u8]) -> String
+{
+    bits.iter().map(|nucl| if *nucl == 1 { '1' } else { '0' }).collect()
+}
+
+fn bits_from_string(bits: &str) -> Result<Vec<u8>, String>
+{
+    bits.chars()
+        .map(|nucl| match nucl {
+            '0' => Ok(0),
+            '1' => Ok(1),
+            _ => Err(format!("Invalid dna nucleotide: {}", nucl))
+        })
+        .collect()
+}
+
+fn get_usize(value: &Value, key: &str) -> Result<usize, String>
+{
+    value[key]
+        .as_u64()
+        .map(|number| number as usize)
+        .ok_or(format!("{} is not found", key))
+}
+
+fn get_indexes(value: &Value, key: &str, indexes_count: usize) -> Result<Vec<usize>, String>
+{
+    let mut indexes = Vec::new();
+
+    for index_value in get_array(value, key)?
+    {
+        let index = index_value.as_u64().ok_or(format!("{} contains invalid index", key))? as usize;
+
+        if index >= indexes_count
+        {
+            return Err(format!("{} contains index out of tree", key));
+        }
+
+        indexes.push(index);
+    }
+
+    Ok(indexes)
+}
+
+fn get_array<'a>(value: &'a Value, key: &str) -> Result<&'a Vec<Value>, String>
+{
+    value[key]
+        .as_array()
+        .ok_or(format!("{} is not found", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dna_data_round_trip() {
+        let mut dna_data = DnaData::new(4, 1, 2, 3);
+
+        dna_data.body_nodes[1] = 1;
+        dna_data.set_mastery_effect_indexes(0, &[2, 5]);
+        dna_data.fitness_score = 0.5;
+        dna_data.fitness_score_targets = vec![0.25, 1.0];
+
+        let loaded_dna_data = dna_data_from_json(&dna_data_to_json(&dna_data), CHECKPOINT_VERSION).unwrap();
+
+        assert_eq!(loaded_dna_data.body_nodes, dna_data.body_nodes);
+        assert_eq!(loaded_dna_data.body_masteries, dna_data.body_masteries);
+        assert_eq!(loaded_dna_data.max_count_nodes, 3);
+        assert_eq!(loaded_dna_data.fitness_score, 0.5);
+        assert_eq!(loaded_dna_data.fitness_score_targets, vec![0.25, 1.0]);
+    }
+
+    #[test]
+    fn dna_data_of_bits_version_is_migrated() {
+        let value = json!({
+            "nodes": "101",
+            "masteries": "010010",
+            "maxCountNodes": 2,
+            "fitnessScore": 0.5,
+            "fitnessScoreTargets": [0.5]
+        });
+
+        let dna_data = dna_data_from_json(&value, CHECKPOINT_BITS_VERSION).unwrap();
+
+        assert_eq!(dna_data.body_nodes, vec![1, 0, 1]);
+        assert_eq!(dna_data.get_mastery_effect_indexes(0), vec![1, 4]);
+        assert_eq!(dna_data.fitness_score_targets, vec![0.5]);
+    }
+
+    #[test]
+    fn dna_data_of_another_tree_is_rejected() {
+        let dna_data = DnaData::new(4, 2, 1, 3);
+
+        assert!(check_dna_data_sizes(&dna_data, 4, 2, 1).is_ok());
+        assert!(check_dna_data_sizes(&dna_data, 5, 2, 1).is_err());
+        assert!(check_dna_data_sizes(&dna_data, 4, 1, 1).is_err());
+        assert!(check_dna_data_sizes(&dna_data, 4, 2, 2).is_err());
+    }
+}
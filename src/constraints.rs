@@ -0,0 +1,149 @@
+use sss_moo::Constraint;
+use crate::dna::Dna;
+use crate::fitness_function_calculator::MIN_TARGET_MULTIPLIER;
+use crate::stop_criteria::TARGET_MET_FITNESS_SCORE;
+use crate::target::Target;
+
+// Objective values of feasible dnas are far below it, even for maximized stats
+const INFEASIBLE_OBJECTIVE_VALUE: f64 = 1e15;
+
+// Dna which misses any of constraint targets gets the same value of every objective, it grows with violation.
+// So infeasible dnas are ranked below all feasible ones, and the less violating one dominates the other
+pub struct TargetsConstraint
+{}
+
+impl Constraint<Dna> for TargetsConstraint
+{
+    fn value(&self, dna: &Dna, value: f64) -> f64
+    {
+        let violation = get_constraint_violation(dna);
+
+        if violation > 0.0
+        {
+            INFEASIBLE_OBJECTIVE_VALUE * (1.0 + violation)
+        }
+        else
+        {
+            value
+        }
+    }
+}
+
+pub fn get_constraint_target_indexes(targets: &[Box<dyn Target>]) -> Vec<usize>
+{
+    targets
+        .iter()
+        .enumerate()
+        .filter(|(_, target)| target.is_constraint())
+        .map(|(target_index, _)| target_index)
+        .collect()
+}
+
+// Share of target value which is not reached, from 0 for met target to 1 for nothing reached
+pub fn get_target_violation(target_score: f64) -> f64
+{
+    if target_score >= TARGET_MET_FITNESS_SCORE
+    {
+        return 0.0;
+    }
+
+    ((1.0 - target_score) / (1.0 - MIN_TARGET_MULTIPLIER)).clamp(0.0, 1.0)
+}
+
+// Violations of every target, 0 for targets which are not constraints
+pub fn get_constraint_violations(dna: &Dna) -> Vec<f64>
+{
+    let mut violations = vec![0.0; dna.fitness_score_targets.len()];
+
+    for target_index in &dna.context.constraint_target_indexes
+    {
+        violations[*target_index] = get_target_violation(dna.fitness_score_targets[*target_index]);
+    }
+
+    violations
+}
+
+pub fn get_constraint_violation(dna: &Dna) -> f64
+{
+    dna.context.constraint_target_indexes
+        .iter()
+        .map(|target_index| get_target_violation(dna.fitness_score_targets[*target_index]))
+        .sum()
+}
+
+// Less violating dna is better, fitness score decides between equally violating ones
+pub fn is_better_dna(dna: &Dna, other_dna: &Dna) -> bool
+{
+    let violation = get_constraint_violation(dna);
+    let other_violation = get_constraint_violation(other_dna);
+
+    if violation != other_violation
+    {
+        return violation < other_violation;
+    }
+
+    dna.fitness_score > other_dna.fitness_score
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::dna::{DnaContext, DnaData};
+    use super::*;
+
+    // The first target is a constraint, the second one is not
+    fn create_dna(fitness_score_targets: [f64; 2], fitness_score: f64) -> Dna
+    {
+        let context = Arc::new(DnaContext {
+            constraint_target_indexes: vec![0],
+            ..DnaContext::default()
+        });
+
+        let mut dna = Dna::new_with_context(DnaData::new(1, 0, 2, 1), context);
+
+        dna.fitness_score_targets.copy_from_slice(&fitness_score_targets);
+        dna.fitness_score = fitness_score;
+
+        dna
+    }
+
+    #[test]
+    fn met_constraint_has_no_violation() {
+        assert_eq!(get_target_violation(1.0), 0.0);
+        assert_eq!(get_target_violation(MIN_TARGET_MULTIPLIER), 1.0);
+        assert!((get_target_violation(0.505) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn only_constraint_targets_are_violated() {
+        let dna = create_dna([0.505, 0.01], 0.5);
+
+        let violations = get_constraint_violations(&dna);
+
+        assert!((violations[0] - 0.5).abs() < 1e-9);
+        assert_eq!(violations[1], 0.0);
+    }
+
+    #[test]
+    fn infeasible_dna_is_ranked_below_feasible_ones() {
+        let feasible_dna = create_dna([1.0, 0.01], 0.01);
+        let infeasible_dna = create_dna([0.9, 1.0], 0.9);
+        let more_infeasible_dna = create_dna([0.5, 1.0], 0.5);
+
+        let constraint = TargetsConstraint {};
+
+        assert_eq!(constraint.value(&feasible_dna, -1e6), -1e6);
+        assert!(constraint.value(&infeasible_dna, -1e6) > 1e6);
+        assert!(constraint.value(&infeasible_dna, -1e6) < constraint.value(&more_infeasible_dna, -1e6));
+
+        assert!(is_better_dna(&feasible_dna, &infeasible_dna));
+        assert!(is_better_dna(&infeasible_dna, &more_infeasible_dna));
+        assert!(!is_better_dna(&infeasible_dna, &feasible_dna));
+    }
+
+    #[test]
+    fn fitness_decides_between_equally_violating_dnas() {
+        assert!(is_better_dna(&create_dna([1.0, 0.5], 0.5), &create_dna([1.0, 0.25], 0.25)));
+        assert!(!is_better_dna(&create_dna([1.0, 0.25], 0.25), &create_dna([1.0, 0.5], 0.5)));
+    }
+}
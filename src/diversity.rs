@@ -0,0 +1,68 @@
+use std::cmp::Ordering;
+
+// Mean of Hamming distances between every pair of sorted node sets, 0 for less than two sets
+pub fn mean_hamming_distance(node_sets: &[&[i64]]) -> f64
+{
+    let mut distances_sum = 0;
+    let mut pairs_count = 0;
+
+    for (set_index, node_set) in node_sets.iter().enumerate()
+    {
+        for other_node_set in &node_sets[set_index + 1..]
+        {
+            distances_sum += hamming_distance(node_set, other_node_set);
+            pairs_count += 1;
+        }
+    }
+
+    if pairs_count == 0
+    {
+        return 0.0;
+    }
+
+    distances_sum as f64 / pairs_count as f64
+}
+
+// Count of nodes which are in only one of sorted sets
+fn hamming_distance(node_set: &[i64], other_node_set: &[i64]) -> usize
+{
+    let mut common_count = 0;
+    let mut position = 0;
+    let mut other_position = 0;
+
+    while position < node_set.len() && other_position < other_node_set.len()
+    {
+        match node_set[position].cmp(&other_node_set[other_position]) {
+            Ordering::Less => position += 1,
+            Ordering::Greater => other_position += 1,
+            Ordering::Equal => {
+                common_count += 1;
+                position += 1;
+                other_position += 1;
+            }
+        }
+    }
+
+    node_set.len() + other_node_set.len() - 2 * common_count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hamming_distance_counts_nodes_of_one_set() {
+        assert_eq!(hamming_distance(&[1, 2, 3], &[2, 3, 4, 5]), 3);
+        assert_eq!(hamming_distance(&[1, 2], &[1, 2]), 0);
+        assert_eq!(hamming_distance(&[], &[7]), 1);
+    }
+
+    #[test]
+    fn mean_hamming_distance_is_taken_over_pairs() {
+        let node_sets: Vec<&[i64]> = vec![&[1, 2], &[1, 3], &[1, 2, 3]];
+
+        // Distances are 2, 1 and 1
+        assert!((mean_hamming_distance(&node_sets) - 4.0 / 3.0).abs() < 1e-9);
+        assert_eq!(mean_hamming_distance(&node_sets[..1]), 0.0);
+    }
+}
@@ -1,10 +1,22 @@
-use std::ops::{Deref, DerefMut, RangeInclusive};
+use std::ops::{Deref, DerefMut, Range, RangeInclusive};
 use std::rc::Rc;
-use mlua::UserData;
-use rand::prelude::SliceRandom;
-use rand::{Rng, thread_rng};
+use std::sync::{Arc, Mutex};
+use mlua::{Lua, UserData, UserDataMethods};
+use mlua::prelude::{LuaError, LuaResult};
+use rand::prelude::{SliceRandom, StdRng};
+use rand::{Rng, SeedableRng};
+use crate::solve_options::DEFAULT_MAX_MUTATE_CLUSTER_SIZE;
+use crate::repair::{repair_dna, RepairPolicy};
+use crate::constraints::get_constraint_violations;
+use crate::tree_graph::TreeGraph;
+
+// Every mastery group has an ordered list of effect choices, allocated nodes of the group take them in this order.
+// A gene is effect index + 1, NO_MASTERY_CHOICE ends the list
+pub const MASTERY_CHOICES_COUNT: usize = 6;
+pub const NO_MASTERY_CHOICE: u8 = 0;
+// Choice is written by one symbol of serialized dna
+pub const MAX_MASTERY_EFFECTS_COUNT: usize = 63;
 
-const MAX_MUTATE_CLUSTER_SIZE: usize = 4;
 
 #[derive(Clone)]
 pub struct LuaDna
@@ -12,10 +24,200 @@ pub struct LuaDna
     pub reference: Rc<Dna>
 }
 
-impl UserData for LuaDna {}
+impl UserData for LuaDna {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("GetFitnessScore", |_lua_context, this, ()| {
+            Ok(this.reference.fitness_score)
+        });
+
+        methods.add_method("GetTargetScores", |_lua_context, this, ()| {
+            Ok(this.reference.fitness_score_targets.clone())
+        });
+
+        // Violation of every target from 0 to 1, targets which are not constraints have 0
+        methods.add_method("GetConstraintViolations", |_lua_context, this, ()| {
+            Ok(get_constraint_violations(&this.reference))
+        });
+
+        // Indexes of node genes, from 0 in the order of DnaEncoder tree nodes
+        methods.add_method("GetSelectedNodeIndexes", |_lua_context, this, ()| {
+            Ok(selected_indexes(&this.reference.body_nodes))
+        });
+
+        methods.add_method("GetSelectedMasteryEffects", |lua_context, this, ()| {
+            let effects_table = lua_context.create_table()?;
+
+            // Choices of a mastery go in the order they are taken
+            let mut number = 1;
+
+            for mastery_index in 0..this.reference.get_masteries_count()
+            {
+                for effect_index in this.reference.get_mastery_effect_indexes(mastery_index)
+                {
+                    let effect_table = lua_context.create_table()?;
+
+                    effect_table.set("masteryIndex", mastery_index)?;
+                    effect_table.set("effectIndex", effect_index)?;
+
+                    effects_table.set(number, effect_table)?;
+
+                    number += 1;
+                }
+            }
+
+            Ok(effects_table)
+        });
+
+        methods.add_method("Clone", |_lua_context, this, ()| {
+            Ok(LuaDna {
+                reference: Rc::new(this.reference.as_ref().clone())
+            })
+        });
+
+        methods.add_method("Serialize", |_lua_context, this, ()| {
+            Ok(this.reference.serialize())
+        });
+
+        // Replaces genes and scores of this dna, context of this dna is kept
+        methods.add_method_mut("Deserialize", |_lua_context, this, serialized_dna: String| {
+            let dna_data = DnaData::deserialize(&serialized_dna).map_err(LuaError::RuntimeError)?;
+
+            this.reference = Rc::new(Dna::new_with_context(dna_data, this.reference.context.clone()));
+
+            Ok(())
+        });
+    }
+}
+
+pub fn lua_deserialize_dna(_: &Lua, serialized_dna: String) -> LuaResult<LuaDna>
+{
+    let dna_data = DnaData::deserialize(&serialized_dna).map_err(LuaError::RuntimeError)?;
+
+    Ok(LuaDna {
+        reference: Rc::new(Dna::new_with_context(dna_data, Arc::new(DnaContext::default())))
+    })
+}
+
+fn selected_indexes(body: &[u8]) -> Vec<usize>
+{
+    body.iter()
+        .enumerate()
+        .filter(|(_, nucl)| **nucl == 1)
+        .map(|(index, _)| index)
+        .collect()
+}
 
 pub struct Dna {
-    pub reference: Box<DnaData>
+    pub reference: Box<DnaData>,
+    pub context: Arc<DnaContext>
+}
+
+// Shared by all dnas of a solve session
+pub struct DnaContext {
+    pub locked_node_indexes: Vec<usize>,
+    pub forbidden_node_indexes: Vec<usize>,
+    pub max_mutate_cluster_size: usize,
+    pub mutation_mode: MutationMode,
+    pub crossover_mode: CrossoverMode,
+    pub repair_policy: RepairPolicy,
+    // Targets which are hard constraints, see TargetsConstraint
+    pub constraint_target_indexes: Vec<usize>,
+    // Known when solve is started with build
+    pub tree_graph: Option<Arc<TreeGraph>>,
+    // Drives every random choice of genetic operators, so seeded runs can be repeated
+    pub rng: Mutex<StdRng>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MutationMode
+{
+    // Flips a run of neighbouring genes
+    Cluster,
+    // Grows or prunes a branch, or moves a leaf of the allocated tree
+    Graph
+}
+
+impl MutationMode
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            MutationMode::Cluster => "cluster",
+            MutationMode::Graph => "graph"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MutationMode>
+    {
+        match name {
+            "cluster" => Some(MutationMode::Cluster),
+            "graph" => Some(MutationMode::Graph),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CrossoverMode
+{
+    // Takes a range of gene indexes from the second parent, bits of masteries too
+    Range,
+    // Takes a connected region of the tree from the second parent and whole mastery groups from either parent.
+    // Falls back to range without tree graph
+    Region
+}
+
+impl CrossoverMode
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            CrossoverMode::Range => "range",
+            CrossoverMode::Region => "region"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<CrossoverMode>
+    {
+        match name {
+            "range" => Some(CrossoverMode::Range),
+            "region" => Some(CrossoverMode::Region),
+            _ => None
+        }
+    }
+}
+
+impl Default for DnaContext {
+    fn default() -> Self {
+        DnaContext {
+            locked_node_indexes: vec![],
+            forbidden_node_indexes: vec![],
+            max_mutate_cluster_size: DEFAULT_MAX_MUTATE_CLUSTER_SIZE,
+            mutation_mode: MutationMode::Cluster,
+            crossover_mode: CrossoverMode::Region,
+            repair_policy: RepairPolicy::Random,
+            constraint_target_indexes: vec![],
+            tree_graph: None,
+            rng: Mutex::new(StdRng::from_entropy())
+        }
+    }
+}
+
+impl DnaContext {
+    // Same settings with own generator, so threads which use it do not change random sequences of each other
+    pub fn with_seed(&self, seed: u64) -> DnaContext {
+        DnaContext {
+            locked_node_indexes: self.locked_node_indexes.clone(),
+            forbidden_node_indexes: self.forbidden_node_indexes.clone(),
+            max_mutate_cluster_size: self.max_mutate_cluster_size,
+            mutation_mode: self.mutation_mode,
+            crossover_mode: self.crossover_mode,
+            repair_policy: self.repair_policy,
+            constraint_target_indexes: self.constraint_target_indexes.clone(),
+            tree_graph: self.tree_graph.clone(),
+            rng: Mutex::new(StdRng::seed_from_u64(seed))
+        }
+    }
 }
 
 impl<'a> Deref for Dna {
@@ -33,41 +235,376 @@ pub struct DnaData {
     pub body_masteries: Vec<u8>,
     pub max_count_nodes: usize,
     pub fitness_score: f64,
-    pub fitness_score_targets: Vec<f64>
+    pub fitness_score_targets: Vec<f64>,
+    // Selected nodes which decoder could not allocate within budget, known after evaluation
+    pub unreachable_node_indexes: Vec<usize>,
+    // Key and sorted node ids of decoded tree, known after evaluation
+    pub decoded_tree_key: Option<u64>,
+    pub allocated_node_ids: Vec<i64>
 }
 
 impl DnaData {
     pub(crate) fn new(tree_nodes_count: usize, mastery_count: usize, targets_count: usize, max_count_nodes: usize) -> DnaData {
         DnaData {
             body_nodes: vec![0; tree_nodes_count],
-            body_masteries: vec![0; mastery_count * 6],
+            body_masteries: vec![NO_MASTERY_CHOICE; mastery_count * MASTERY_CHOICES_COUNT],
             max_count_nodes,
             fitness_score: -1.0,
-            fitness_score_targets: vec![-1.0; targets_count]
+            fitness_score_targets: vec![-1.0; targets_count],
+            unreachable_node_indexes: vec![],
+            decoded_tree_key: None,
+            allocated_node_ids: vec![]
+        }
+    }
+
+    // Decoded tree is not known either until dna is evaluated again
+    pub fn reset_fitness_scores(&mut self) {
+        self.fitness_score = -1.0;
+        self.fitness_score_targets.fill(-1.0);
+        self.decoded_tree_key = None;
+        self.allocated_node_ids.clear();
+    }
+
+    pub fn get_masteries_count(&self) -> usize {
+        self.body_masteries.len() / MASTERY_CHOICES_COUNT
+    }
+
+    // Effect choices of mastery in order, repeated choices are skipped as they cannot be taken twice
+    pub fn get_mastery_effect_indexes(&self, mastery_index: usize) -> Vec<usize> {
+        let choices_start = mastery_index * MASTERY_CHOICES_COUNT;
+
+        let mut effect_indexes = Vec::new();
+
+        if let Some(choices) = self.body_masteries.get(choices_start..choices_start + MASTERY_CHOICES_COUNT)
+        {
+            for choice in choices.iter().take_while(|choice| **choice != NO_MASTERY_CHOICE)
+            {
+                let effect_index = (*choice - 1) as usize;
+
+                if !effect_indexes.contains(&effect_index)
+                {
+                    effect_indexes.push(effect_index);
+                }
+            }
+        }
+
+        effect_indexes
+    }
+
+    pub fn set_mastery_effect_indexes(&mut self, mastery_index: usize, effect_indexes: &[usize]) {
+        let choices_start = mastery_index * MASTERY_CHOICES_COUNT;
+
+        let choices = &mut self.body_masteries[choices_start..choices_start + MASTERY_CHOICES_COUNT];
+
+        choices.fill(NO_MASTERY_CHOICE);
+
+        let valid_effect_indexes = effect_indexes
+            .iter()
+            .filter(|effect_index| **effect_index < MAX_MASTERY_EFFECTS_COUNT);
+
+        for (choice, effect_index) in choices.iter_mut().zip(valid_effect_indexes)
+        {
+            *choice = (*effect_index + 1) as u8;
+        }
+    }
+}
+
+// Genes of first format had a bit for each of six effects of mastery, effects were taken in order of bits
+pub fn mastery_bits_to_choices(mastery_bits: &[u8]) -> Result<Vec<u8>, String> {
+    if !mastery_bits.len().is_multiple_of(MASTERY_CHOICES_COUNT)
+    {
+        return Err(format!("Invalid mastery genes count: {}", mastery_bits.len()));
+    }
+
+    let mut mastery_choices = Vec::with_capacity(mastery_bits.len());
+
+    for mastery_bits in mastery_bits.chunks(MASTERY_CHOICES_COUNT)
+    {
+        let choices: Vec<u8> = mastery_bits
+            .iter()
+            .enumerate()
+            .filter(|(_, nucl)| **nucl == 1)
+            .map(|(effect_index, _)| (effect_index + 1) as u8)
+            .collect();
+
+        mastery_choices.extend_from_slice(&choices);
+        mastery_choices.resize(mastery_choices.len() + MASTERY_CHOICES_COUNT - choices.len(), NO_MASTERY_CHOICE);
+    }
+
+    Ok(mastery_choices)
+}
+
+pub fn validate_mastery_choices(mastery_choices: &[u8]) -> Result<(), String> {
+    if !mastery_choices.len().is_multiple_of(MASTERY_CHOICES_COUNT)
+    {
+        return Err(format!("Invalid mastery genes count: {}", mastery_choices.len()));
+    }
+
+    match mastery_choices.iter().find(|choice| **choice as usize > MAX_MASTERY_EFFECTS_COUNT) {
+        Some(choice) => Err(format!("Invalid mastery choice: {}", choice)),
+        None => Ok(())
+    }
+}
+
+// Compact form: "<version>.<nodes count>.<mastery genes count>.<max count nodes>.<node genes>.<mastery genes>",
+// node genes are packed by bits and mastery genes take a symbol each, both in url safe base64. Scores are not kept.
+// First version had no mastery genes part, masteries were packed by bits with nodes
+const SERIALIZED_DNA_VERSION: &str = "2";
+const SERIALIZED_DNA_BITS_VERSION: &str = "1";
+const SERIALIZED_DNA_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+impl DnaData {
+    pub fn serialize(&self) -> String {
+        let mastery_genes: String = self.body_masteries
+            .iter()
+            .map(|choice| SERIALIZED_DNA_ALPHABET[*choice as usize] as char)
+            .collect();
+
+        format!(
+            "{}.{}.{}.{}.{}.{}",
+            SERIALIZED_DNA_VERSION,
+            self.body_nodes.len(),
+            self.body_masteries.len(),
+            self.max_count_nodes,
+            encode_genes(&self.body_nodes),
+            mastery_genes
+        )
+    }
+
+    pub fn deserialize(serialized_dna: &str) -> Result<DnaData, String> {
+        let parts: Vec<&str> = serialized_dna.trim().split('.').collect();
+
+        let parts_count =
+            match parts[0] {
+                SERIALIZED_DNA_VERSION => 6,
+                SERIALIZED_DNA_BITS_VERSION => 5,
+                version => return Err(format!("Unsupported serialized dna version: {}", version))
+            };
+
+        if parts.len() != parts_count
+        {
+            return Err(String::from("Serialized dna has invalid format"));
+        }
+
+        let parse_count = |part: &str| part
+            .parse::<usize>()
+            .map_err(|_| format!("Serialized dna has invalid number: {}", part));
+
+        let nodes_count = parse_count(parts[1])?;
+        let mastery_genes_count = parse_count(parts[2])?;
+        let max_count_nodes = parse_count(parts[3])?;
+
+        // Every tree has nodes, and dna cannot select more nodes than tree has
+        if nodes_count == 0 || max_count_nodes > nodes_count
+        {
+            return Err(format!("Serialized dna has invalid nodes count: {} with max count nodes {}", nodes_count, max_count_nodes));
+        }
+
+        if !mastery_genes_count.is_multiple_of(MASTERY_CHOICES_COUNT)
+        {
+            return Err(format!("Serialized dna has invalid mastery genes count: {}", mastery_genes_count));
+        }
+
+        let (body_nodes, body_masteries) =
+            if parts[0] == SERIALIZED_DNA_BITS_VERSION
+            {
+                let mut genes = decode_genes(parts[4], nodes_count + mastery_genes_count)?;
+                let mastery_bits = genes.split_off(nodes_count);
+
+                (genes, mastery_bits_to_choices(&mastery_bits)?)
+            }
+            else
+            {
+                if parts[5].len() != mastery_genes_count
+                {
+                    return Err(String::from("Serialized dna mastery genes do not match its size"));
+                }
+
+                let body_masteries = parts[5]
+                    .bytes()
+                    .map(decode_symbol)
+                    .collect::<Result<Vec<u8>, String>>()?;
+
+                (decode_genes(parts[4], nodes_count)?, body_masteries)
+            };
+
+        Ok(DnaData {
+            body_nodes,
+            body_masteries,
+            max_count_nodes,
+            fitness_score: -1.0,
+            fitness_score_targets: vec![],
+            unreachable_node_indexes: vec![],
+            decoded_tree_key: None,
+            allocated_node_ids: vec![]
+        })
+    }
+}
+
+fn encode_genes(genes: &[u8]) -> String {
+    genes
+        .chunks(6)
+        .map(|chunk| {
+            let symbol_index = chunk
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (bit_index, nucl)| acc | ((*nucl as usize & 1) << bit_index));
+
+            SERIALIZED_DNA_ALPHABET[symbol_index] as char
+        })
+        .collect()
+}
+
+fn decode_genes(encoded_genes: &str, genes_count: usize) -> Result<Vec<u8>, String> {
+    if encoded_genes.len() != genes_count.div_ceil(6)
+    {
+        return Err(String::from("Serialized dna genes do not match its size"));
+    }
+
+    let mut genes = Vec::with_capacity(genes_count);
+
+    for symbol in encoded_genes.bytes()
+    {
+        let symbol_index = decode_symbol(symbol)? as usize;
+
+        for bit_index in 0..6
+        {
+            if genes.len() < genes_count
+            {
+                genes.push(((symbol_index >> bit_index) & 1) as u8);
+            }
         }
     }
+
+    Ok(genes)
+}
+
+fn decode_symbol(symbol: u8) -> Result<u8, String> {
+    SERIALIZED_DNA_ALPHABET
+        .iter()
+        .position(|alphabet_symbol| *alphabet_symbol == symbol)
+        .map(|symbol_index| symbol_index as u8)
+        .ok_or(format!("Serialized dna has invalid symbol: {}", symbol as char))
 }
 
 impl Clone for Dna {
     fn clone(&self) -> Dna {
         Dna {
-            reference: self.reference.clone()
+            reference: self.reference.clone(),
+            context: self.context.clone()
         }
     }
 }
 
 impl Dna {
-    pub fn new(dna_data: DnaData) -> Dna {
-        Dna {
-            reference: Box::new(dna_data)
+    pub fn new_with_context(dna_data: DnaData, context: Arc<DnaContext>) -> Dna {
+        let mut dna = Dna {
+            reference: Box::new(dna_data),
+            context
+        };
+
+        dna.apply_node_locks();
+
+        dna
+    }
+
+    pub fn apply_node_locks(&mut self) {
+        let context = self.context.clone();
+
+        for locked_node_index in &context.locked_node_indexes
+        {
+            self.body_nodes[*locked_node_index] = 1;
+        }
+
+        for forbidden_node_index in &context.forbidden_node_indexes
+        {
+            self.body_nodes[*forbidden_node_index] = 0;
         }
     }
 
     pub fn mutate(&mut self) {
-        let mut rng = thread_rng();
+        let context = self.context.clone();
+        let mut rng = context.rng.lock().unwrap();
 
         // Mutate nodes
-        let mutate_cluster_size = rng.gen_range(1..=MAX_MUTATE_CLUSTER_SIZE);
+        match (context.mutation_mode, &context.tree_graph) {
+            (MutationMode::Graph, Some(tree_graph)) => self.mutate_nodes_by_graph(&mut rng, tree_graph),
+            _ => self.mutate_nodes_cluster(&mut rng)
+        }
+
+        self.apply_node_locks();
+
+        self.mutate_mastery_choices(&mut rng);
+
+        // repair_dna takes generator again
+        drop(rng);
+
+        repair_dna(self);
+    }
+
+    // Changes, removes or reorders one effect choice of a random mastery
+    fn mutate_mastery_choices(&mut self, rng: &mut StdRng) {
+        let masteries_count = self.get_masteries_count();
+
+        if masteries_count == 0
+        {
+            return;
+        }
+
+        let mastery_index = rng.gen_range(0..masteries_count);
+
+        let mut effect_indexes = self.get_mastery_effect_indexes(mastery_index);
+
+        let other_effect_indexes: Vec<usize> = (0..self.get_mastery_effects_count(mastery_index))
+            .filter(|effect_index| !effect_indexes.contains(effect_index))
+            .collect();
+
+        match rng.gen_range(0..3) {
+            0 => {
+                if let Some(other_effect_index) = other_effect_indexes.choose(rng)
+                {
+                    // Position past the last choice adds a new one
+                    let position = rng.gen_range(0..=effect_indexes.len().min(MASTERY_CHOICES_COUNT - 1));
+
+                    if position == effect_indexes.len()
+                    {
+                        effect_indexes.push(*other_effect_index);
+                    }
+                    else
+                    {
+                        effect_indexes[position] = *other_effect_index;
+                    }
+                }
+            },
+            1 => {
+                if !effect_indexes.is_empty()
+                {
+                    effect_indexes.remove(rng.gen_range(0..effect_indexes.len()));
+                }
+            },
+            _ => {
+                if effect_indexes.len() > 1
+                {
+                    let position = rng.gen_range(1..effect_indexes.len());
+
+                    effect_indexes.swap(position - 1, position);
+                }
+            }
+        }
+
+        self.set_mastery_effect_indexes(mastery_index, &effect_indexes);
+    }
+
+    // Effects count is known from tree graph, without it every choice is allowed
+    pub fn get_mastery_effects_count(&self, mastery_index: usize) -> usize {
+        match &self.context.tree_graph {
+            Some(tree_graph) if mastery_index < tree_graph.mastery_effects_counts.len() => tree_graph.get_mastery_effects_count(mastery_index),
+            _ => MASTERY_CHOICES_COUNT
+        }
+    }
+
+    fn mutate_nodes_cluster(&mut self, rng: &mut StdRng) {
+        let mutate_cluster_size = rng.gen_range(1..=self.context.max_mutate_cluster_size);
         let start_num = rng.gen_range(0..self.body_nodes.len() - mutate_cluster_size);
 
         let body_slice = &mut self.body_nodes[start_num..start_num+mutate_cluster_size];
@@ -82,35 +619,152 @@ impl Dna {
                 *nucl = 1;
             }
         }
+    }
+
+    // Cluster size bounds length of grown or pruned branch
+    fn mutate_nodes_by_graph(&mut self, rng: &mut StdRng, tree_graph: &TreeGraph) {
+        let branch_size = rng.gen_range(1..=self.context.max_mutate_cluster_size);
+
+        let is_mutated =
+            match rng.gen_range(0..3) {
+                0 => self.grow_branch(rng, tree_graph, branch_size),
+                1 => self.prune_branch(rng, tree_graph, branch_size),
+                _ => self.move_leaf(rng, tree_graph)
+            };
+
+        // Empty tree has nothing to prune or move
+        if !is_mutated && !self.grow_branch(rng, tree_graph, branch_size)
+        {
+            self.mutate_nodes_cluster(rng);
+        }
+    }
 
-        // Mutate masteries
-        let mutate_cluster_size = 1;
-        let start_num = rng.gen_range(0..self.body_masteries.len() - mutate_cluster_size);
+    fn grow_branch(&mut self, rng: &mut StdRng, tree_graph: &TreeGraph, branch_size: usize) -> bool {
+        let frontier_indexes: Vec<usize> = tree_graph.get_frontier_indexes(&self.body_nodes)
+            .into_iter()
+            .filter(|node_index| !self.context.forbidden_node_indexes.contains(node_index))
+            .collect();
 
-        let body_slice = &mut self.body_masteries[start_num..start_num+mutate_cluster_size];
+        let mut node_index =
+            match frontier_indexes.choose(rng) {
+                None => return false,
+                Some(node_index) => *node_index
+            };
 
-        for nucl in body_slice.iter_mut() {
-            if *nucl == 1
-            {
-                *nucl = 0;
+        for _ in 0..branch_size
+        {
+            self.body_nodes[node_index] = 1;
+
+            let next_indexes: Vec<usize> = tree_graph.linked_indexes[node_index]
+                .iter()
+                .filter(|linked_index| self.body_nodes[**linked_index] == 0
+                    && !tree_graph.root_indexes.contains(linked_index)
+                    && !self.context.forbidden_node_indexes.contains(linked_index))
+                .cloned()
+                .collect();
+
+            match next_indexes.choose(rng) {
+                None => break,
+                Some(next_index) => node_index = *next_index
             }
-            else
-            {
-                *nucl = 1;
+        }
+
+        true
+    }
+
+    fn prune_branch(&mut self, rng: &mut StdRng, tree_graph: &TreeGraph, branch_size: usize) -> bool {
+        let mut node_index =
+            match self.choose_unlocked_leaf(rng, tree_graph) {
+                None => return false,
+                Some(node_index) => node_index
+            };
+
+        for _ in 0..branch_size
+        {
+            self.body_nodes[node_index] = 0;
+
+            // Pruning goes on while the rest of branch ends with a leaf
+            let parent_index = tree_graph.linked_indexes[node_index]
+                .iter()
+                .find(|linked_index| self.body_nodes[**linked_index] == 1)
+                .cloned();
+
+            match parent_index {
+                Some(parent_index) if !self.context.locked_node_indexes.contains(&parent_index)
+                    && tree_graph.is_leaf(&self.body_nodes, parent_index) => node_index = parent_index,
+                _ => break
+            }
+        }
+
+        true
+    }
+
+    fn move_leaf(&mut self, rng: &mut StdRng, tree_graph: &TreeGraph) -> bool {
+        let leaf_index =
+            match self.choose_unlocked_leaf(rng, tree_graph) {
+                None => return false,
+                Some(leaf_index) => leaf_index
+            };
+
+        self.body_nodes[leaf_index] = 0;
+
+        let frontier_indexes: Vec<usize> = tree_graph.get_frontier_indexes(&self.body_nodes)
+            .into_iter()
+            .filter(|node_index| *node_index != leaf_index && !self.context.forbidden_node_indexes.contains(node_index))
+            .collect();
+
+        match frontier_indexes.choose(rng) {
+            None => {
+                self.body_nodes[leaf_index] = 1;
+
+                false
+            },
+            Some(node_index) => {
+                self.body_nodes[*node_index] = 1;
+
+                true
             }
         }
     }
 
+    fn choose_unlocked_leaf(&self, rng: &mut StdRng, tree_graph: &TreeGraph) -> Option<usize> {
+        let leaf_indexes: Vec<usize> = tree_graph.get_leaf_indexes(&self.body_nodes)
+            .into_iter()
+            .filter(|node_index| !self.context.locked_node_indexes.contains(node_index))
+            .collect();
+
+        leaf_indexes.choose(rng).cloned()
+    }
+
     pub fn combine(&self, dna2: &Dna) -> Dna {
-        let mut rng = thread_rng();
+        if let (CrossoverMode::Region, Some(tree_graph)) = (self.context.crossover_mode, &self.context.tree_graph)
+        {
+            return Dna::crossover_dna_by_region(self, dna2, tree_graph);
+        }
+
+        let mut rng = self.context.rng.lock().unwrap();
 
         let crossover_body_start: usize = rng.gen_range(0..self.body_nodes.len());
         let crossover_body_end: usize = rng.gen_range(0..self.body_nodes.len());
 
-        let crossover_masteries_start: usize = rng.gen_range(0..self.body_masteries.len());
-        let crossover_masteries_end: usize = rng.gen_range(crossover_masteries_start..self.body_masteries.len());
+        let masteries_count = self.get_masteries_count();
 
-        let range_masteries_nodes = crossover_masteries_start..=crossover_masteries_end;
+        // Choices of a mastery are kept together, tree can have no masteries at all
+        let range_masteries_nodes =
+            if masteries_count > 0
+            {
+                let crossover_masteries_start: usize = rng.gen_range(0..masteries_count);
+                let crossover_masteries_end: usize = rng.gen_range(crossover_masteries_start..masteries_count);
+
+                crossover_masteries_start * MASTERY_CHOICES_COUNT..(crossover_masteries_end + 1) * MASTERY_CHOICES_COUNT
+            }
+            else
+            {
+                0..0
+            };
+
+        // crossover_dna takes generator again
+        drop(rng);
 
         if crossover_body_start < crossover_body_end
         {
@@ -131,34 +785,126 @@ impl Dna {
     fn crossover_dna(dna1: &Dna,
                      dna2: &Dna,
                      range_body_nodes: RangeInclusive<usize>,
-                     range_masteries_nodes: RangeInclusive<usize>) -> Dna
+                     range_masteries_nodes: Range<usize>) -> Dna
     {
         let mut new_dna = dna1.clone();
 
         new_dna.body_nodes[range_body_nodes.clone()].clone_from_slice(&dna2.body_nodes[range_body_nodes]);
         new_dna.body_masteries[range_masteries_nodes.clone()].clone_from_slice(&dna2.body_masteries[range_masteries_nodes]);
 
-        let mut selected_nodes = Vec::new();
-        for (index, nucl) in new_dna.body_nodes.iter().enumerate()
-        {
-            if *nucl == 1
+        new_dna.apply_node_locks();
+
+        repair_dna(&mut new_dna);
+
+        new_dna
+    }
+
+    fn crossover_dna_by_region(dna1: &Dna, dna2: &Dna, tree_graph: &TreeGraph) -> Dna
+    {
+        let mut new_dna = dna1.clone();
+
+        let mut rng = dna1.context.rng.lock().unwrap();
+
+        let is_difference: Vec<bool> = dna1.body_nodes
+            .iter()
+            .zip(dna2.body_nodes.iter())
+            .map(|(nucl1, nucl2)| nucl1 != nucl2)
+            .collect();
+
+        let difference_components = tree_graph.get_components(&is_difference);
+
+        // Either a whole branch where parents differ, or a ball around a node of one of parents
+        let region_indexes =
+            if !difference_components.is_empty() && rng.gen_bool(0.5)
             {
-                selected_nodes.push(index);
+                difference_components.choose(&mut *rng).unwrap().clone()
             }
-        }
+            else
+            {
+                let selected_indexes: Vec<usize> = (0..dna1.body_nodes.len())
+                    .filter(|node_index| dna1.body_nodes[*node_index] == 1 || dna2.body_nodes[*node_index] == 1)
+                    .collect();
+
+                match selected_indexes.choose(&mut *rng) {
+                    None => vec![],
+                    Some(center_index) => {
+                        let max_region_nodes_count = rng.gen_range(1..=selected_indexes.len());
+
+                        tree_graph.get_ball_indexes(*center_index, max_region_nodes_count)
+                    }
+                }
+            };
 
-        if selected_nodes.len() > new_dna.max_count_nodes
+        for node_index in region_indexes
         {
-            selected_nodes.shuffle(&mut thread_rng());
+            new_dna.body_nodes[node_index] = dna2.body_nodes[node_index];
+        }
 
-            while selected_nodes.len() > new_dna.max_count_nodes
+        // Choices of a mastery are taken together, so their order from a parent is kept
+        for (new_group, group2) in new_dna.body_masteries
+            .chunks_mut(MASTERY_CHOICES_COUNT)
+            .zip(dna2.body_masteries.chunks(MASTERY_CHOICES_COUNT))
+        {
+            if rng.gen_bool(0.5)
             {
-                let index = selected_nodes.pop().unwrap();
-
-                new_dna.body_nodes[index] = 0;
+                new_group.clone_from_slice(group2);
             }
         }
 
+        // repair_dna takes generator again
+        drop(rng);
+
+        new_dna.apply_node_locks();
+
+        repair_dna(&mut new_dna);
+
         new_dna
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialized_dna_round_trip() {
+        let mut dna_data = DnaData::new(13, 2, 1, 5);
+
+        dna_data.body_nodes[0] = 1;
+        dna_data.body_nodes[6] = 1;
+        dna_data.body_nodes[12] = 1;
+        dna_data.set_mastery_effect_indexes(0, &[3, 0]);
+        dna_data.set_mastery_effect_indexes(1, &[MAX_MASTERY_EFFECTS_COUNT - 1]);
+
+        let serialized_dna = dna_data.serialize();
+
+        assert!(serialized_dna.starts_with("2.13.12.5."));
+
+        let deserialized_dna = DnaData::deserialize(&serialized_dna).unwrap();
+
+        assert_eq!(deserialized_dna.body_nodes, dna_data.body_nodes);
+        assert_eq!(deserialized_dna.body_masteries, dna_data.body_masteries);
+        assert_eq!(deserialized_dna.max_count_nodes, 5);
+        assert_eq!(deserialized_dna.get_mastery_effect_indexes(0), vec![3, 0]);
+    }
+
+    #[test]
+    fn serialized_dna_of_first_version_is_migrated() {
+        // Nodes 1,0,1 and mastery bits 0,1,0,0,1,0 packed by 6 bits: 0b010101 is 'V', 0b010 is 'C'
+        let dna_data = DnaData::deserialize("1.3.6.2.VC").unwrap();
+
+        assert_eq!(dna_data.body_nodes, vec![1, 0, 1]);
+        assert_eq!(dna_data.body_masteries, vec![2, 5, NO_MASTERY_CHOICE, NO_MASTERY_CHOICE, NO_MASTERY_CHOICE, NO_MASTERY_CHOICE]);
+        assert_eq!(dna_data.get_mastery_effect_indexes(0), vec![1, 4]);
+        assert_eq!(dna_data.max_count_nodes, 2);
+    }
+
+    #[test]
+    fn serialized_dna_of_invalid_size_is_rejected() {
+        assert!(DnaData::deserialize("2.3.6.2.F").is_err());
+        assert!(DnaData::deserialize("2.3.5.2.F.AAAAA").is_err());
+        assert!(DnaData::deserialize("2.3.6.4.F.AAAAAA").is_err());
+        assert!(DnaData::deserialize("2.0.0.0..").is_err());
+        assert!(DnaData::deserialize("3.3.6.2.F.AAAAAA").is_err());
+    }
+}
@@ -1,52 +1,95 @@
-#![allow(dead_code)]
-// Experimental cache feature
-
-use std::borrow::Borrow;
 use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::dna::Dna;
+use crate::dna_encoder::DnaConvertResult;
 
-use crate::dna::{Dna};
+// Stops growing after this size, every entry keeps scores of all targets
+const MAX_CACHE_ENTRIES_COUNT: usize = 1_000_000;
 
 #[derive(Hash)]
-pub struct DnaKey<'a>(&'a [u8], &'a [u8]);
+pub struct PhenotypeKey<'a>(&'a [i64], &'a [(i64, i64)]);
+
+#[derive(Clone)]
+struct CachedFitness
+{
+    fitness_score: f64,
+    fitness_score_targets: Vec<f64>
+}
 
+// Fitness of decoded trees, shared by all workers of a solve session
+#[derive(Default)]
 pub struct DnaCacheFitness
 {
-    pub(crate) cache_map: HashMap<u64, f64>
+    cache_map: RwLock<HashMap<u64, CachedFitness>>,
+    hits_count: AtomicUsize,
+    misses_count: AtomicUsize
+}
+
+pub struct DnaCacheStatistics
+{
+    pub hits_count: usize,
+    pub misses_count: usize,
+    pub entries_count: usize
 }
 
 impl DnaCacheFitness {
-    pub fn try_get_fitness_score_by_dna(&self, dna: &Dna) -> Option<f64>
+    pub fn get_key(dna_convert_result: &DnaConvertResult) -> u64
     {
         let mut hasher = DefaultHasher::new();
 
-        DnaKey{
-            0: dna.reference.body_nodes.borrow(),
-            1: dna.reference.body_masteries.borrow()
-        }.hash(&mut hasher);
+        PhenotypeKey(
+            &dna_convert_result.allocated_node_ids,
+            &dna_convert_result.selected_mastery_effects
+        ).hash(&mut hasher);
 
-        let key = hasher.finish();
+        hasher.finish()
+    }
 
-        let res = self.cache_map.get(&key);
+    // Copies cached scores into dna, returns false when tree was not evaluated yet
+    pub fn try_apply_fitness_scores(&self, key: u64, dna: &mut Dna) -> bool
+    {
+        let cache_map = self.cache_map.read().unwrap();
 
-        match res {
-            Some(res) => Some(res.clone()),
-            None => None
+        match cache_map.get(&key) {
+            Some(cached_fitness) if cached_fitness.fitness_score_targets.len() == dna.fitness_score_targets.len() => {
+                dna.fitness_score = cached_fitness.fitness_score;
+                dna.fitness_score_targets.copy_from_slice(&cached_fitness.fitness_score_targets);
+
+                self.hits_count.fetch_add(1, Ordering::Relaxed);
+
+                true
+            },
+            _ => {
+                self.misses_count.fetch_add(1, Ordering::Relaxed);
+
+                false
+            }
         }
     }
 
-    pub fn set_fitness_score_by_dna(&mut self, dna: &Dna, fitness_score: f64)
+    pub fn set_fitness_scores(&self, key: u64, dna: &Dna)
     {
-        let mut hasher = DefaultHasher::new();
-
-        DnaKey{
-            0: dna.reference.body_nodes.borrow(),
-            1: dna.reference.body_masteries.borrow()
-        }.hash(&mut hasher);
+        let mut cache_map = self.cache_map.write().unwrap();
 
-        let key = hasher.finish();
+        if cache_map.len() < MAX_CACHE_ENTRIES_COUNT
+        {
+            cache_map.insert(key, CachedFitness {
+                fitness_score: dna.fitness_score,
+                fitness_score_targets: dna.fitness_score_targets.clone()
+            });
+        }
+    }
 
-        self.cache_map.insert(key, fitness_score);
+    pub fn get_statistics(&self) -> DnaCacheStatistics
+    {
+        DnaCacheStatistics {
+            hits_count: self.hits_count.load(Ordering::Relaxed),
+            misses_count: self.misses_count.load(Ordering::Relaxed),
+            entries_count: self.cache_map.read().unwrap().len()
+        }
     }
 }
@@ -1,14 +1,17 @@
 use std::borrow::{Borrow};
 use std::cell::{RefCell};
 use std::collections::{HashMap, HashSet};
-use mlua::{Lua, TableExt, UserData, UserDataMethods};
-use mlua::prelude::{LuaResult, LuaTable, LuaValue};
-use crate::dna::{Dna, LuaDna};
+use std::fmt::Display;
+use mlua::{FromLua, Lua, TableExt, ToLua, UserData, UserDataMethods};
+use mlua::prelude::{LuaError, LuaResult, LuaTable, LuaValue};
+use crate::dna::{Dna, DnaData, LuaDna, MASTERY_CHOICES_COUNT, MAX_MASTERY_EFFECTS_COUNT};
+use crate::tree_graph::TreeGraph;
 
 pub struct DnaEncoder
 {
     tree_nodes: Vec<RefCell<Node>>,
     masteries: Vec<RefCell<Mastery>>,
+    node_id_index_map: HashMap<i64, usize>,
 
     path_indexes_buf: Vec<usize>,
     index_nodes_to_allocate: HashSet<usize>,
@@ -18,24 +21,41 @@ pub struct DnaEncoder
 pub struct DnaConvertResult
 {
     pub allocated_normal_nodes: usize,
-    pub allocated_ascend_nodes: usize
+    pub allocated_ascend_nodes: usize,
+    // Decoded tree, dnas with the same tree have the same stats
+    pub allocated_node_ids: Vec<i64>,
+    pub selected_mastery_effects: Vec<(i64, i64)>,
+    // Node genes which are selected, but could not be allocated within budget
+    pub unreachable_node_indexes: Vec<usize>
 }
 
 impl DnaConvertResult {
-    pub fn get_table<'a>(&self, lua_context: &'a Lua) -> LuaTable<'a>
+    pub fn get_table<'a>(&self, lua_context: &'a Lua) -> LuaResult<LuaTable<'a>>
     {
-        let res_table = lua_context.create_table().unwrap();
+        let res_table = lua_context.create_table()?;
 
-        res_table.set("usedNormalNodeCount", self.allocated_normal_nodes).unwrap();
-        res_table.set("usedAscendancyNodeCount", self.allocated_ascend_nodes).unwrap();
+        res_table.set("usedNormalNodeCount", self.allocated_normal_nodes)?;
+        res_table.set("usedAscendancyNodeCount", self.allocated_ascend_nodes)?;
 
-        res_table
+        Ok(res_table)
     }
 }
 
 impl DnaEncoder {
-    pub fn convert_dna_to_build(&mut self, build_table: &LuaTable, dna: &Dna, max_number_normal_nodes_to_allocate: usize, max_number_ascend_nodes_to_allocate: usize) -> DnaConvertResult
+    pub fn convert_dna_to_build(&mut self, build_table: &LuaTable, dna: &Dna, max_number_normal_nodes_to_allocate: usize, max_number_ascend_nodes_to_allocate: usize) -> LuaResult<DnaConvertResult>
     {
+        let dna_convert_result = self.decode_dna(dna, max_number_normal_nodes_to_allocate, max_number_ascend_nodes_to_allocate)?;
+
+        self.apply_to_build(build_table, &dna_convert_result)?;
+
+        Ok(dna_convert_result)
+    }
+
+    // Decoded tree is enough for keys of caches, so build is not touched until stats are calculated
+    pub fn decode_dna(&mut self, dna: &Dna, max_number_normal_nodes_to_allocate: usize, max_number_ascend_nodes_to_allocate: usize) -> LuaResult<DnaConvertResult>
+    {
+        self.validate_dna(dna).map_err(|err| LuaError::RuntimeError(format!("DnaEncoder: {}", err)))?;
+
         let mut queue_indexes = Vec::new();
 
         std::mem::swap(&mut queue_indexes, &mut self.queue_indexes_buffer);
@@ -56,6 +76,19 @@ impl DnaEncoder {
             }
 
             node.path_indexes.clear();
+
+            node.is_locked = false;
+            node.is_forbidden = false;
+        }
+
+        for locked_node_index in &dna.context.locked_node_indexes
+        {
+            self.tree_nodes[*locked_node_index].borrow_mut().is_locked = true;
+        }
+
+        for forbidden_node_index in &dna.context.forbidden_node_indexes
+        {
+            self.tree_nodes[*forbidden_node_index].borrow_mut().is_forbidden = true;
         }
 
         for mastery in &self.masteries
@@ -83,51 +116,52 @@ impl DnaEncoder {
 
         for (tree_node_index, nucl) in dna.body_nodes.iter().enumerate()
         {
-            if *nucl == 1
+            if *nucl == 1 && !self.tree_nodes[tree_node_index].borrow().is_forbidden
             {
                 self.index_nodes_to_allocate.insert(tree_node_index);
             }
         }
 
-        for (index, nucl) in dna.body_masteries.iter().enumerate()
+        for locked_node_index in &dna.context.locked_node_indexes
         {
-            if *nucl == 1
-            {
-                let mastery_index = index / 6;
-                let effect_index = index % 6;
+            self.index_nodes_to_allocate.insert(*locked_node_index);
+        }
 
-                let mut mastery = self.masteries[mastery_index].borrow_mut();
+        for (mastery_index, mastery) in self.masteries.iter().enumerate()
+        {
+            let mut mastery = mastery.borrow_mut();
 
-                if effect_index < mastery.effects.len()
-                {
-                    mastery.effects_indexes_to_select.push(effect_index);
-                }
-            }
-        }
+            let effects_count = mastery.effects.len();
 
-        let spec_table: LuaTable = build_table.get("spec").unwrap();
-        let mastery_selections_table: LuaTable = spec_table.get("masterySelections").unwrap();
-        let tree_table: LuaTable = spec_table.get("tree").unwrap();
-        let mastery_effects_table: LuaTable = tree_table.get("masteryEffects").unwrap();
-        let _: LuaValue = spec_table.call_method("ResetNodes", 0).unwrap();
-        let nodes_table: LuaTable = spec_table.get("nodes").unwrap();
-        let alloc_nodes_table: LuaTable = spec_table.get("allocNodes").unwrap();
+            mastery.effects_indexes_to_select = dna.get_mastery_effect_indexes(mastery_index)
+                .into_iter()
+                .filter(|effect_index| *effect_index < effects_count)
+                .collect();
+        }
 
         let mut allocated_normal_nodes = 0;
         let mut allocated_ascend_nodes = 0;
+        let mut allocated_node_ids = Vec::new();
+        let mut selected_mastery_effects = Vec::new();
         while self.index_nodes_to_allocate.is_empty() == false
         {
             let mut smallest_node_index = usize::MAX;
             let mut smallest_node_path_dist = 0;
+            let mut smallest_node_is_locked = false;
 
+            // Locked nodes are allocated first, StartSolve checks that they fit into nodes budget
             for index_node in &self.index_nodes_to_allocate
             {
                 let node = self.tree_nodes[index_node.clone()].borrow();
 
-                if smallest_node_index == usize::MAX || smallest_node_path_dist > node.path_dist || (smallest_node_path_dist == node.path_dist && smallest_node_index > *index_node)
+                if smallest_node_index == usize::MAX
+                    || (node.is_locked && !smallest_node_is_locked)
+                    || (node.is_locked == smallest_node_is_locked
+                        && (smallest_node_path_dist > node.path_dist || (smallest_node_path_dist == node.path_dist && smallest_node_index > *index_node)))
                 {
                     smallest_node_path_dist = node.path_dist;
                     smallest_node_index = index_node.clone();
+                    smallest_node_is_locked = node.is_locked;
                 }
             }
 
@@ -206,18 +240,7 @@ impl DnaEncoder {
 
                                         let effect_id = mastery.effects[effect_index].id;
 
-                                        mastery_selections_table.set(path_node.id, effect_id).unwrap();
-
-                                        let effect_table: LuaTable = mastery_effects_table.get(effect_id).unwrap();
-
-                                        let lua_sd: LuaValue = effect_table.get("sd").unwrap();
-
-                                        let node_table: LuaTable = nodes_table.get(path_node.id).unwrap();
-
-                                        node_table.set("sd", lua_sd).unwrap();
-                                        node_table.set("allMasteryOptions", false).unwrap();
-
-                                        let _: LuaValue = tree_table.call_method("ProcessStats", node_table).unwrap();
+                                        selected_mastery_effects.push((path_node.id, effect_id));
 
                                         mastery.effect_next_select_index += 1;
 
@@ -240,9 +263,7 @@ impl DnaEncoder {
                         {
                             let mut path_node = path_node.borrow_mut();
 
-                            let node_table: LuaTable = nodes_table.get(path_node.id).unwrap();
-                            node_table.set("alloc", true).unwrap();
-                            alloc_nodes_table.set(path_node.id, node_table).unwrap();
+                            allocated_node_ids.push(path_node.id);
 
                             path_node.alloc = true;
 
@@ -272,10 +293,177 @@ impl DnaEncoder {
         // restore buffers
         std::mem::swap(&mut queue_indexes, &mut self.queue_indexes_buffer);
 
-        DnaConvertResult {
+        allocated_node_ids.sort_unstable();
+        selected_mastery_effects.sort_unstable();
+
+        let unreachable_node_indexes = dna.body_nodes
+            .iter()
+            .enumerate()
+            .filter(|(node_index, nucl)| **nucl == 1 && !self.tree_nodes[*node_index].borrow().alloc)
+            .map(|(node_index, _)| node_index)
+            .collect();
+
+        Ok(DnaConvertResult {
             allocated_normal_nodes,
-            allocated_ascend_nodes
+            allocated_ascend_nodes,
+            allocated_node_ids,
+            selected_mastery_effects,
+            unreachable_node_indexes
+        })
+    }
+
+    // Allocates decoded tree in build spec
+    pub fn apply_to_build(&self, build_table: &LuaTable, dna_convert_result: &DnaConvertResult) -> LuaResult<()>
+    {
+        let spec_table: LuaTable = get_field(build_table, "build", "spec")?;
+
+        allocate_tree(&spec_table, &dna_convert_result.allocated_node_ids, &dna_convert_result.selected_mastery_effects)
+    }
+
+    // Locked nodes are allocated before others, so the ones which do not fit for empty dna never fit
+    pub fn get_unallocated_locked_node_ids(&mut self, dna: &Dna, max_number_normal_nodes_to_allocate: usize, max_number_ascend_nodes_to_allocate: usize) -> LuaResult<Vec<i64>>
+    {
+        self.decode_dna(dna, max_number_normal_nodes_to_allocate, max_number_ascend_nodes_to_allocate)?;
+
+        Ok(
+            dna.context.locked_node_indexes
+                .iter()
+                .map(|locked_node_index| self.tree_nodes[*locked_node_index].borrow())
+                .filter(|node| !node.alloc)
+                .map(|node| node.id)
+                .collect()
+        )
+    }
+
+    // Dna of another tree version would index nodes and masteries out of this tree
+    pub fn validate_dna(&self, dna: &Dna) -> Result<(), String>
+    {
+        if dna.body_nodes.len() != self.tree_nodes.len()
+        {
+            return Err(format!("dna has {} node genes, but tree has {} nodes", dna.body_nodes.len(), self.tree_nodes.len()));
+        }
+
+        if dna.body_masteries.len() != self.masteries.len() * MASTERY_CHOICES_COUNT
+        {
+            return Err(format!("dna has {} mastery genes, but tree has {} masteries", dna.body_masteries.len(), self.masteries.len()));
         }
+
+        let context_node_indexes = dna.context.locked_node_indexes
+            .iter()
+            .chain(dna.context.forbidden_node_indexes.iter());
+
+        for node_index in context_node_indexes
+        {
+            if *node_index >= self.tree_nodes.len()
+            {
+                return Err(format!("locked or forbidden node index {} is out of tree", node_index));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_tree_nodes_count(&self) -> usize
+    {
+        self.tree_nodes.len()
+    }
+
+    pub fn get_masteries_count(&self) -> usize
+    {
+        self.masteries.len()
+    }
+
+    pub fn get_tree_graph(&self) -> TreeGraph
+    {
+        TreeGraph {
+            linked_indexes: self.tree_nodes
+                .iter()
+                .map(|node| node.borrow().linked_indexes.clone())
+                .collect(),
+            root_indexes: self.tree_nodes
+                .iter()
+                .filter(|node| RefCell::borrow(node).default_alloc)
+                .map(|node| RefCell::borrow(node).tree_node_index)
+                .collect(),
+            mastery_effects_counts: self.masteries
+                .iter()
+                .map(|mastery| mastery.borrow().effects.len())
+                .collect()
+        }
+    }
+
+    pub fn get_lockable_node_indexes(&self, node_ids: &[i64]) -> Result<Vec<usize>, String>
+    {
+        let mut node_indexes = Vec::with_capacity(node_ids.len());
+
+        for node_id in node_ids
+        {
+            let node_index =
+                match self.node_id_index_map.get(node_id) {
+                    None => return Err(format!("Node {} is not found in tree", node_id)),
+                    Some(node_index) => *node_index
+                };
+
+            let node = self.tree_nodes[node_index].borrow();
+
+            match node.node_type {
+                NodeType::NORMAL => node_indexes.push(node_index),
+                _ => return Err(format!("Node {} ({}) cannot be locked, only normal nodes are supported", node_id, node.name))
+            }
+        }
+
+        Ok(node_indexes)
+    }
+
+    // Reverse of convert_dna_to_build: takes nodes and mastery selections which were allocated in build spec
+    pub fn convert_build_to_dna(&self, build_tree: &BuildTree, targets_count: usize, max_count_nodes: usize) -> DnaData
+    {
+        let mut dna_data = DnaData::new(self.tree_nodes.len(), self.masteries.len(), targets_count, max_count_nodes);
+
+        for node_id in &build_tree.allocated_node_ids
+        {
+            if let Some(node_index) = self.node_id_index_map.get(node_id)
+            {
+                let node = self.tree_nodes[*node_index].borrow();
+
+                if !node.default_alloc
+                {
+                    dna_data.body_nodes[*node_index] = 1;
+                }
+            }
+        }
+
+        let mut masteries_effect_indexes = vec![Vec::new(); self.masteries.len()];
+
+        for (node_id, effect_id) in build_tree.mastery_selections.iter().copied()
+        {
+            let node_index =
+                match self.node_id_index_map.get(&node_id) {
+                    None => continue,
+                    Some(node_index) => *node_index
+                };
+
+            let mastery_index = self.tree_nodes[node_index].borrow().mastery_index;
+
+            let mastery = self.masteries[mastery_index].borrow();
+
+            if let Some(effect_index) = mastery.effects.iter().position(|effect| effect.id == effect_id)
+            {
+                let effect_indexes: &mut Vec<usize> = &mut masteries_effect_indexes[mastery_index];
+
+                if effect_index < MAX_MASTERY_EFFECTS_COUNT && !effect_indexes.contains(&effect_index)
+                {
+                    effect_indexes.push(effect_index);
+                }
+            }
+        }
+
+        for (mastery_index, effect_indexes) in masteries_effect_indexes.iter().enumerate()
+        {
+            dna_data.set_mastery_effect_indexes(mastery_index, effect_indexes);
+        }
+
+        dna_data
     }
 
     // Perform a breadth-first search of the tree, starting from this node, and determine if it is the closest node to any other nodes
@@ -307,6 +495,11 @@ impl DnaEncoder {
             {
                 let mut other = self.tree_nodes[*linked_index].borrow_mut();
 
+                if other.is_forbidden
+                {
+                    continue;
+                }
+
                 match other.node_type {
                     NodeType::NORMAL => {
                         if node.ascendancy_id == other.ascendancy_id || (cur_dist == 1 && other.ascendancy_id == usize::MAX)
@@ -355,15 +548,15 @@ impl DnaEncoder {
 impl UserData for DnaEncoder {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_method_mut("ConvertDnaToBuild", |lua_context, this, (build_table, dna, max_number_normal_nodes_to_allocate, max_number_ascend_nodes_to_allocate): (LuaTable, LuaDna, usize, usize)| {
-            Ok(this.convert_dna_to_build(&build_table, dna.reference.borrow(), max_number_normal_nodes_to_allocate, max_number_ascend_nodes_to_allocate).get_table(lua_context))
+            this.convert_dna_to_build(&build_table, dna.reference.borrow(), max_number_normal_nodes_to_allocate, max_number_ascend_nodes_to_allocate)?.get_table(lua_context)
         });
 
         methods.add_method("GetTreeNodesCount", |_lua_context, this, ()| {
-            Ok(this.tree_nodes.len())
+            Ok(this.get_tree_nodes_count())
         });
 
         methods.add_method("GetMasteryCount", |_lua_context, this, ()| {
-            Ok(this.masteries.len())
+            Ok(this.get_masteries_count())
         });
     }
 }
@@ -389,7 +582,9 @@ struct Node
     path_dist: usize,
     alloc: bool,
     ascendancy_id: usize,
-    default_alloc: bool
+    default_alloc: bool,
+    is_locked: bool,
+    is_forbidden: bool
 }
 
 struct Mastery
@@ -407,19 +602,114 @@ struct MasteryEffect
 
 pub fn lua_create_dna_encoder(_: &Lua, build_table: LuaTable) -> LuaResult<DnaEncoder>
 {
-    Ok(create_dna_encoder(&build_table))
+    create_dna_encoder(&build_table)
+}
+
+// Lua errors of tree tables are wrapped with the path of the field, so the caller can see what is wrong with the build
+fn get_field<'lua, K: ToLua<'lua> + Display + Copy, V: FromLua<'lua>>(table: &LuaTable<'lua>, table_path: &str, key: K) -> LuaResult<V>
+{
+    table
+        .get(key)
+        .map_err(|err| LuaError::RuntimeError(format!("DnaEncoder: {}.{} is missing or invalid: {}", table_path, key, err)))
+}
+
+// Allocates tree in spec, the same way as PassiveSpec does it
+fn allocate_tree(spec_table: &LuaTable, allocated_node_ids: &[i64], mastery_selections: &[(i64, i64)]) -> LuaResult<()>
+{
+    let mastery_selections_table: LuaTable = get_field(spec_table, "spec", "masterySelections")?;
+    let tree_table: LuaTable = get_field(spec_table, "spec", "tree")?;
+    let mastery_effects_table: LuaTable = get_field(&tree_table, "spec.tree", "masteryEffects")?;
+    let _: LuaValue = spec_table.call_method("ResetNodes", 0)?;
+    let nodes_table: LuaTable = get_field(spec_table, "spec", "nodes")?;
+    let alloc_nodes_table: LuaTable = get_field(spec_table, "spec", "allocNodes")?;
+
+    for (node_id, effect_id) in mastery_selections
+    {
+        mastery_selections_table.set(*node_id, *effect_id)?;
+
+        let effect_table: LuaTable = get_field(&mastery_effects_table, "spec.tree.masteryEffects", *effect_id)?;
+
+        let lua_sd: LuaValue = effect_table.get("sd")?;
+
+        let node_table: LuaTable = get_field(&nodes_table, "spec.nodes", *node_id)?;
+
+        node_table.set("sd", lua_sd)?;
+        node_table.set("allMasteryOptions", false)?;
+
+        let _: LuaValue = tree_table.call_method("ProcessStats", node_table)?;
+    }
+
+    for node_id in allocated_node_ids
+    {
+        let node_table: LuaTable = get_field(&nodes_table, "spec.nodes", *node_id)?;
+        node_table.set("alloc", true)?;
+        alloc_nodes_table.set(*node_id, node_table)?;
+    }
+
+    Ok(())
+}
+
+pub fn read_tree_version(build_table: &LuaTable) -> LuaResult<Option<String>>
+{
+    let spec_table: LuaTable = get_field(build_table, "build", "spec")?;
+
+    spec_table.get("treeVersion")
 }
 
-pub fn create_dna_encoder(build_table: &LuaTable) -> DnaEncoder
+// Tree allocated in build
+#[derive(PartialEq, Eq, Debug)]
+pub struct BuildTree
 {
-    let spec_table: LuaTable = build_table.get("spec").unwrap();
+    pub allocated_node_ids: Vec<i64>,
+    // Ordered by node ids, order of Lua pairs is not defined
+    pub mastery_selections: Vec<(i64, i64)>
+}
 
-    let _: LuaValue = spec_table.call_method("ResetNodes", 0).unwrap();
-    let _: LuaValue = spec_table.call_method("BuildAllDependsAndPaths", 0).unwrap();
+pub fn read_build_tree(build_table: &LuaTable) -> LuaResult<BuildTree>
+{
+    let spec_table: LuaTable = get_field(build_table, "build", "spec")?;
+    let alloc_nodes_table: LuaTable = get_field(&spec_table, "spec", "allocNodes")?;
+    let mastery_selections_table: LuaTable = get_field(&spec_table, "spec", "masterySelections")?;
 
-    let nodes_table: LuaTable = spec_table.get("nodes").unwrap();
+    let mut allocated_node_ids = Vec::new();
 
-    let count_nodes = nodes_table.len().unwrap();
+    for alloc_node_entry in alloc_nodes_table.pairs()
+    {
+        let (node_id, _): (i64, LuaValue) = alloc_node_entry?;
+
+        allocated_node_ids.push(node_id);
+    }
+
+    allocated_node_ids.sort_unstable();
+
+    let mut mastery_selections: Vec<(i64, i64)> = Vec::new();
+
+    for mastery_selection_entry in mastery_selections_table.pairs()
+    {
+        mastery_selections.push(mastery_selection_entry?);
+    }
+
+    mastery_selections.sort_unstable();
+
+    Ok(BuildTree {
+        allocated_node_ids,
+        mastery_selections
+    })
+}
+
+// Start nodes are read from reset spec, then tree of build is allocated back, so user does not lose it
+pub fn create_dna_encoder(build_table: &LuaTable) -> LuaResult<DnaEncoder>
+{
+    let spec_table: LuaTable = get_field(build_table, "build", "spec")?;
+
+    let build_tree = read_build_tree(build_table)?;
+
+    let _: LuaValue = spec_table.call_method("ResetNodes", 0)?;
+    let _: LuaValue = spec_table.call_method("BuildAllDependsAndPaths", 0)?;
+
+    let nodes_table: LuaTable = get_field(&spec_table, "spec", "nodes")?;
+
+    let count_nodes = nodes_table.len()?;
 
     let mut tree_nodes = Vec::with_capacity(count_nodes as usize);
 
@@ -428,7 +718,7 @@ pub fn create_dna_encoder(build_table: &LuaTable) -> DnaEncoder
     let mut ascendacy_id_hash = HashMap::new();
     let mut current_ascendancy_id = 0;
 
-    let current_ascend_class_name: String = spec_table.get("curAscendClassName").unwrap();
+    let current_ascend_class_name: String = get_field(&spec_table, "spec", "curAscendClassName")?;
     let selected_ascendancy_id =
             ascendacy_id_hash
                 .entry(current_ascend_class_name)
@@ -438,19 +728,19 @@ pub fn create_dna_encoder(build_table: &LuaTable) -> DnaEncoder
                     new_id
                 }).clone();
 
-    for node_entry in nodes_table.pairs()
+    for node_entry in nodes_table.clone().pairs()
     {
-        let (node_id, lua_node_table): (i64, LuaTable) = node_entry.unwrap();
+        let (node_id, lua_node_table): (i64, LuaTable) = node_entry?;
 
         let lua_node_type =
-            match lua_node_table.get::<&str, Option<String>>("type").unwrap()
+            match lua_node_table.get::<&str, Option<String>>("type")?
             {
-                None => panic!("Type is not found"),
+                None => return Err(LuaError::RuntimeError(format!("DnaEncoder: type of node {} is not found", node_id))),
                 Some(node_name) => node_name
             };
 
         let node_name =
-            match lua_node_table.get::<&str, Option<String>>("name").unwrap()
+            match lua_node_table.get::<&str, Option<String>>("name")?
             {
                 // Cluster nodes doesnt have names
                 None => "".to_string(),
@@ -475,9 +765,9 @@ pub fn create_dna_encoder(build_table: &LuaTable) -> DnaEncoder
                 NodeType::NORMAL
             };
 
-        let node_alloc: bool = lua_node_table.get("alloc").unwrap();
+        let node_alloc = lua_node_table.get::<&str, Option<bool>>("alloc")?.unwrap_or(false);
 
-        let lua_node_ascend_name: Option<String> = lua_node_table.get("ascendancyName").unwrap();
+        let lua_node_ascend_name: Option<String> = lua_node_table.get("ascendancyName")?;
 
         let node_ascend_id =
             match lua_node_ascend_name {
@@ -506,7 +796,9 @@ pub fn create_dna_encoder(build_table: &LuaTable) -> DnaEncoder
             path_dist: 0,
             alloc: false,
             ascendancy_id: node_ascend_id,
-            default_alloc: node_alloc
+            default_alloc: node_alloc,
+            is_locked: false,
+            is_forbidden: false
         };
 
         tree_nodes.push(RefCell::new(node));
@@ -514,8 +806,6 @@ pub fn create_dna_encoder(build_table: &LuaTable) -> DnaEncoder
 
     tree_nodes.sort_unstable_by(|a, b| b.borrow().id.cmp(&a.borrow().id));
 
-    let nodes_table: LuaTable = spec_table.get("nodes").unwrap();
-
     let mut masteries = Vec::new();
     let mut masteries_hash_node_indexes = HashMap::new();
     for (node_index, node) in tree_nodes.iter().enumerate()
@@ -532,10 +822,10 @@ pub fn create_dna_encoder(build_table: &LuaTable) -> DnaEncoder
                         None => {
                             let mut masteries_node_indexes = Vec::new();
 
-                            let node_table: LuaTable = nodes_table.get(node.id).unwrap();
+                            let node_table: LuaTable = get_field(&nodes_table, "spec.nodes", node.id)?;
 
                             let mastery_effects_table =
-                                match node_table.get::<&str, Option<LuaTable>>("masteryEffects").unwrap() {
+                                match node_table.get::<&str, Option<LuaTable>>("masteryEffects")? {
                                     // Clusters nodes is typed as Mastery. So hes doesnt have real mastery effect. Skip it
                                     None => continue,
                                     Some(mastery_effects_table) => mastery_effects_table
@@ -545,9 +835,9 @@ pub fn create_dna_encoder(build_table: &LuaTable) -> DnaEncoder
 
                             for entry_effect in mastery_effects_table.pairs()
                             {
-                                let (_, effect_table): (LuaValue, LuaTable) = entry_effect.unwrap();
+                                let (_, effect_table): (LuaValue, LuaTable) = entry_effect?;
 
-                                let effect_id: i64 = effect_table.get("effect").unwrap();
+                                let effect_id: i64 = get_field(&effect_table, "masteryEffects[]", "effect")?;
 
                                 mastery_effects.push(MasteryEffect {
                                     id: effect_id
@@ -600,31 +890,34 @@ pub fn create_dna_encoder(build_table: &LuaTable) -> DnaEncoder
         }
     }
 
-    let nodes_table: LuaTable = spec_table.get("nodes").unwrap();
     for node_entry in nodes_table.pairs()
     {
-        let (_, lua_node_table): (i64, LuaTable) = node_entry.unwrap();
+        let (_, lua_node_table): (i64, LuaTable) = node_entry?;
+
+        let node_id: i64 = get_field(&lua_node_table, "spec.nodes[]", "id")?;
 
         let table_linked =
-            match lua_node_table.get::<&str, Option<LuaTable>>("linked").unwrap()
+            match lua_node_table.get::<&str, Option<LuaTable>>("linked")?
             {
-                None => panic!("Linked is not found"),
+                None => return Err(LuaError::RuntimeError(format!("DnaEncoder: linked nodes of node {} are not found", node_id))),
                 Some(table_linked) => table_linked
             };
 
-        let node_id: i64 = lua_node_table.get("id").unwrap();
-
-        let node_index = node_id_index_map.get(&node_id).unwrap();
+        let node_index = node_id_index_map
+            .get(&node_id)
+            .ok_or_else(|| LuaError::RuntimeError(format!("DnaEncoder: node {} is not found in spec.nodes", node_id)))?;
 
         let node = &mut tree_nodes[node_index.clone()].borrow_mut();
 
         for linked_node_entry in table_linked.pairs()
         {
-            let (_, lua_linked_node_table): (i64, LuaTable) = linked_node_entry.unwrap();
+            let (_, lua_linked_node_table): (i64, LuaTable) = linked_node_entry?;
 
-            let linked_node_id: i64 = lua_linked_node_table.get("id").unwrap();
+            let linked_node_id: i64 = get_field(&lua_linked_node_table, "spec.nodes[].linked[]", "id")?;
 
-            let linked_node_index = node_id_index_map.get(&linked_node_id).unwrap();
+            let linked_node_index = node_id_index_map
+                .get(&linked_node_id)
+                .ok_or_else(|| LuaError::RuntimeError(format!("DnaEncoder: linked node {} is not found in spec.nodes", linked_node_id)))?;
 
             node.linked_indexes.push(linked_node_index.clone());
         }
@@ -637,13 +930,18 @@ pub fn create_dna_encoder(build_table: &LuaTable) -> DnaEncoder
         });
     }
 
+    allocate_tree(&spec_table, &build_tree.allocated_node_ids, &build_tree.mastery_selections)?;
+
+    let _: LuaValue = spec_table.call_method("BuildAllDependsAndPaths", 0)?;
+
     let tree_nodes_len = tree_nodes.len().clone();
 
-    DnaEncoder {
+    Ok(DnaEncoder {
         tree_nodes,
         masteries,
+        node_id_index_map,
         path_indexes_buf: Vec::with_capacity(1000),
         index_nodes_to_allocate: HashSet::with_capacity(tree_nodes_len),
         queue_indexes_buffer: Vec::with_capacity(tree_nodes_len)
-    }
+    })
 }
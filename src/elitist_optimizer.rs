@@ -0,0 +1,175 @@
+use rand::prelude::StdRng;
+use rand::{Rng, SeedableRng};
+use sss_moo::evaluator::Evaluator;
+use sss_moo::optimizers::Optimizer;
+use sss_moo::{Meta, Ratio, Solution, SolutionsRuntimeProcessor};
+
+use crate::pareto::cmp_objective_values;
+
+#[derive(Clone)]
+struct Candidate<S: Solution> {
+    sol: S,
+    value: f64,
+}
+
+// Generational GA on the first objective of meta: tournament selection,
+// best candidates of each generation are moved unchanged into the next one
+pub struct ElitistOptimizer<'a, S: Solution> {
+    meta: Box<dyn Meta<'a, S> + 'a>,
+    rng: StdRng,
+    elites_count: usize,
+    tournament_size: usize,
+    best_solutions: Vec<(Vec<f64>, S)>,
+}
+
+impl<'a, S> Optimizer<S> for ElitistOptimizer<'a, S>
+    where
+        S: Solution,
+{
+    fn name(&self) -> &str {
+        "Elitist GA"
+    }
+
+    fn optimize(&mut self, eval: &mut Box<dyn Evaluator>, runtime_solutions_processor: &mut Box<dyn SolutionsRuntimeProcessor<S>>) {
+        let pop_size = self.meta.population_size();
+        let crossover_odds = self.meta.crossover_odds();
+        let mutation_odds = self.meta.mutation_odds();
+
+        let mut pop: Vec<S> = (0..pop_size)
+            .map(|_| self.meta.random_solution())
+            .collect();
+
+        runtime_solutions_processor.new_candidates(pop.iter_mut().collect());
+
+        let mut parent_pop = self.sort(pop);
+
+        for iter in 0.. {
+            if runtime_solutions_processor.needs_early_stop()
+            {
+                break;
+            }
+
+            runtime_solutions_processor.iteration_num(iter);
+
+            runtime_solutions_processor.iter_solutions(
+                parent_pop.iter_mut()
+                    .map(|candidate| &mut candidate.sol)
+                    .collect()
+            );
+
+            // Processor can replace candidates, for example by migration or duplicates elimination
+            parent_pop = self.sort(parent_pop.into_iter().map(|candidate| candidate.sol).collect());
+
+            self.best_solutions.clear();
+            self.best_solutions.push((vec![parent_pop[0].value], parent_pop[0].sol.clone()));
+
+            if self.meta.objectives()[0].good_enough(parent_pop[0].value)
+            {
+                break;
+            }
+
+            if eval.can_terminate(iter, parent_pop.iter().map(|candidate| vec![candidate.value]).collect())
+            {
+                break;
+            }
+
+            let children_count = pop_size - self.elites_count;
+
+            let mut child_pop: Vec<S> = Vec::with_capacity(children_count + 1);
+
+            while child_pop.len() < children_count {
+                let mut c1 = self.tournament(&parent_pop).clone();
+                let mut c2 = self.tournament(&parent_pop).clone();
+
+                if self.odds(crossover_odds) {
+                    c1.crossover(&mut c2);
+                };
+
+                if self.odds(mutation_odds) {
+                    c1.mutate();
+                };
+
+                if self.odds(mutation_odds) {
+                    c2.mutate();
+                };
+
+                child_pop.push(c1);
+                child_pop.push(c2);
+            }
+
+            child_pop.truncate(children_count);
+
+            runtime_solutions_processor.new_candidates(child_pop.iter_mut().collect());
+
+            let next_pop: Vec<S> = parent_pop
+                .into_iter()
+                .take(self.elites_count)
+                .map(|candidate| candidate.sol)
+                .chain(child_pop)
+                .collect();
+
+            parent_pop = self.sort(next_pop);
+        }
+    }
+
+    fn best_solutions(&self) -> Vec<(Vec<f64>, S)> {
+        self.best_solutions.clone()
+    }
+}
+
+impl<'a, S> ElitistOptimizer<'a, S>
+    where
+        S: Solution,
+{
+    pub fn new(meta: impl Meta<'a, S> + 'a, seed: u64, elites_count: usize, tournament_size: usize) -> Self {
+        ElitistOptimizer {
+            meta: Box::new(meta),
+            rng: StdRng::seed_from_u64(seed),
+            elites_count,
+            tournament_size,
+            best_solutions: Vec::new(),
+        }
+    }
+
+    fn odds(&mut self, ratio: &Ratio) -> bool {
+        self.rng.gen_ratio(ratio.0, ratio.1)
+    }
+
+    // Population is sorted, so the smallest of random indexes is the best candidate
+    fn tournament<'b>(&mut self, pop: &'b [Candidate<S>]) -> &'b S {
+        let winner_index = (0..self.tournament_size)
+            .map(|_| self.rng.gen_range(0..pop.len()))
+            .min()
+            .unwrap_or(0);
+
+        &pop[winner_index].sol
+    }
+
+    // Best candidate first, objective values are minimized
+    fn sort(&self, pop: Vec<S>) -> Vec<Candidate<S>> {
+        let mut candidates: Vec<Candidate<S>> = pop
+            .into_iter()
+            .map(|sol| {
+                let value = self.value(&sol);
+
+                Candidate {
+                    sol,
+                    value
+                }
+            })
+            .collect();
+
+        candidates.sort_by(|a, b| cmp_objective_values(a.value, b.value));
+
+        candidates
+    }
+
+    fn value(&self, s: &S) -> f64 {
+        let objective = &self.meta.objectives()[0];
+
+        self.meta
+            .constraints()
+            .iter()
+            .fold(objective.value(s), |acc, cons| cons.value(s, acc))
+    }
+}
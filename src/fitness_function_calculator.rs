@@ -1,20 +1,62 @@
 use std::collections::HashMap;
-use mlua::prelude::LuaTable;
+use mlua::prelude::{LuaError, LuaResult, LuaTable};
 
+use crate::stat_cache::StatKey;
 use crate::target::Target;
 
-const MIN_TARGET_MULTIPLIER: f64 = 0.01;
+pub const MIN_TARGET_MULTIPLIER: f64 = 0.01;
+
+// How target scores with their weights give fitness score
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FitnessAggregation
+{
+    // Product of scores raised to their weights
+    Product,
+    // Product with weights divided by their sum, so fitness score does not grow with weights
+    GeometricMean,
+    // Sum of weighted scores
+    Sum,
+    // The worst weighted score
+    Chebyshev
+}
+
+impl FitnessAggregation
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            FitnessAggregation::Product => "product",
+            FitnessAggregation::GeometricMean => "geometricMean",
+            FitnessAggregation::Sum => "sum",
+            FitnessAggregation::Chebyshev => "chebyshev"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<FitnessAggregation>
+    {
+        match name {
+            "product" => Some(FitnessAggregation::Product),
+            "geometricMean" => Some(FitnessAggregation::GeometricMean),
+            "sum" => Some(FitnessAggregation::Sum),
+            "chebyshev" => Some(FitnessAggregation::Chebyshev),
+            _ => None
+        }
+    }
+}
 
 pub struct FitnessFunctionCalculator
 {
-    pub(crate) targets: Vec<Box<dyn Target>>
+    pub(crate) targets: Vec<Box<dyn Target>>,
+    aggregation: FitnessAggregation
 }
 
 pub struct FitnessFunctionCalculatorStats<'a>
 {
-    stats_env: &'a LuaTable<'a>,
+    // Stats are read only from stat_values when there is no env
+    stats_env: Option<&'a LuaTable<'a>>,
     actor_outputs: HashMap<String, LuaTable<'a>>,
-    stat_values: HashMap<String, Option<f64>>
+    stat_values: HashMap<StatKey, Option<f64>>,
+    is_missing_stats: bool
 }
 
 impl<'a> FitnessFunctionCalculatorStats<'a>
@@ -22,51 +64,117 @@ impl<'a> FitnessFunctionCalculatorStats<'a>
     pub fn new(stats_env: &'a LuaTable<'_>) ->  Self
     {
         FitnessFunctionCalculatorStats {
-            stats_env,
+            stats_env: Some(stats_env),
             actor_outputs: HashMap::with_capacity(2),
             stat_values: Default::default(),
+            is_missing_stats: false
         }
     }
 
-    pub fn try_get_stat(&mut self, actor: String, stat: String) -> Option<f64> {
-        let actor_output_table = self.actor_outputs
-            .entry(actor.clone())
-            .or_insert_with(|| {
-                let actor_table = self.stats_env.get::<&str, LuaTable>(actor.as_str()).unwrap();
+    pub fn from_cached_stats(stat_values: HashMap<StatKey, Option<f64>>) -> Self
+    {
+        FitnessFunctionCalculatorStats {
+            stats_env: None,
+            actor_outputs: HashMap::new(),
+            stat_values,
+            is_missing_stats: false
+        }
+    }
+
+    pub fn try_get_stat(&mut self, actor: String, stat: String) -> LuaResult<Option<f64>> {
+        let stat_key = (actor, stat);
+
+        if let Some(stat_value) = self.stat_values.get(&stat_key)
+        {
+            return Ok(*stat_value);
+        }
+
+        let stats_env =
+            match self.stats_env {
+                None => {
+                    self.is_missing_stats = true;
 
-                actor_table.get::<&str, LuaTable>("output").unwrap()
-            });
+                    return Ok(None);
+                },
+                Some(stats_env) => stats_env
+            };
+
+        let (actor, stat) = &stat_key;
+
+        if !self.actor_outputs.contains_key(actor)
+        {
+            let actor_table = stats_env.get::<&str, Option<LuaTable>>(actor.as_str())?
+                .ok_or_else(|| LuaError::RuntimeError(format!("Stats: actor {} is not found", actor)))?;
+
+            let output_table = actor_table.get::<&str, Option<LuaTable>>("output")?
+                .ok_or_else(|| LuaError::RuntimeError(format!("Stats: output of actor {} is not found", actor)))?;
+
+            self.actor_outputs.insert(actor.clone(), output_table);
+        }
+
+        let stat_value = self.actor_outputs[actor].get::<&str, Option<f64>>(stat.as_str())?;
+
+        self.stat_values.insert(stat_key, stat_value);
+
+        Ok(stat_value)
+    }
 
-        self.stat_values
-            .entry(stat.clone())
-            .or_insert_with(|| {
-                actor_output_table.get::<&str, Option<f64>>(stat.as_str()).unwrap()
-            }).clone()
+    // Cached stats have no value for some of targets, scores calculated from them are wrong
+    pub fn is_missing_stats(&self) -> bool {
+        self.is_missing_stats
+    }
+
+    pub fn get_stat_values(&self) -> &HashMap<StatKey, Option<f64>> {
+        &self.stat_values
     }
 }
 
 impl FitnessFunctionCalculator
 {
-    pub fn new(targets: Vec<Box<dyn Target>>) -> Self
+    pub fn new(targets: Vec<Box<dyn Target>>, aggregation: FitnessAggregation) -> Self
     {
         FitnessFunctionCalculator{
-            targets
+            targets,
+            aggregation
         }
     }
 
-    pub fn calculate_and_get_fitness_score<'a>(&self, stats: &mut FitnessFunctionCalculatorStats<'a>) -> f64
+    pub fn aggregate_fitness_score(&self, target_scores: &[f64]) -> f64
     {
-        let mut score = 1.0;
+        let contributions = self.get_target_contributions(target_scores);
 
-        for target in &self.targets
-        {
-            score *= target.calc_fitness_score(self, stats);
+        match self.aggregation {
+            FitnessAggregation::Product | FitnessAggregation::GeometricMean => contributions.iter().product(),
+            FitnessAggregation::Sum => contributions.iter().sum(),
+            FitnessAggregation::Chebyshev => contributions.into_iter().reduce(f64::min).unwrap_or(1.0)
         }
+    }
+
+    // Term of fitness score for every target: factors of product and geometric mean,
+    // addends of sum, and values which minimum is taken for Chebyshev
+    pub fn get_target_contributions(&self, target_scores: &[f64]) -> Vec<f64>
+    {
+        let weights_sum: f64 = self.targets.iter().map(|target| target.get_weight()).sum();
 
-        score
+        self.targets
+            .iter()
+            .zip(target_scores)
+            .map(|(target, score)| {
+                let weight = target.get_weight();
+
+                // Negative stats of maximized targets would give NaN for fractional weights
+                match self.aggregation {
+                    FitnessAggregation::Product => score.max(0.0).powf(weight),
+                    FitnessAggregation::GeometricMean if weights_sum > 0.0 => score.max(0.0).powf(weight / weights_sum),
+                    FitnessAggregation::GeometricMean => 1.0,
+                    FitnessAggregation::Sum | FitnessAggregation::Chebyshev => weight * score
+                }
+            })
+            .collect()
     }
 
-    pub(crate) fn calc_target_mul(&self, mut x: f64, _weight: f64, mut target: f64, lower_is_better: bool) -> f64
+    // Weight is applied by aggregation of fitness score
+    pub(crate) fn calc_target_mul(&self, mut x: f64, mut target: f64, lower_is_better: bool) -> f64
     {
         if x < 0.0
         {
@@ -112,3 +220,106 @@ impl FitnessFunctionCalculator
         MIN_TARGET_MULTIPLIER + (1.0 - MIN_TARGET_MULTIPLIER) * ratio
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use super::*;
+
+    #[derive(Clone)]
+    struct WeightedTarget
+    {
+        weight: f64
+    }
+
+    impl Target for WeightedTarget
+    {
+        fn clone_dyn(&self) -> Box<dyn Target> {
+            Box::new(self.clone())
+        }
+
+        fn calc_fitness_score(&self, _: &FitnessFunctionCalculator, _: &mut FitnessFunctionCalculatorStats) -> LuaResult<f64> {
+            Ok(1.0)
+        }
+
+        fn get_maximize_value(&self, _: &mut FitnessFunctionCalculatorStats) -> LuaResult<f64> {
+            Ok(0.0)
+        }
+
+        fn get_weight(&self) -> f64 {
+            self.weight
+        }
+
+        fn to_json(&self) -> Value {
+            Value::Null
+        }
+
+        fn is_user_requirement(&self) -> bool {
+            true
+        }
+
+        fn is_constraint(&self) -> bool {
+            false
+        }
+
+        fn get_auto_target_name(&self) -> Option<&str> {
+            None
+        }
+    }
+
+    fn create_calculator(aggregation: FitnessAggregation) -> FitnessFunctionCalculator
+    {
+        FitnessFunctionCalculator::new(vec![Box::new(WeightedTarget { weight: 1.0 }), Box::new(WeightedTarget { weight: 3.0 })], aggregation)
+    }
+
+    fn assert_close(value: f64, expected_value: f64)
+    {
+        assert!((value - expected_value).abs() < 1e-9, "{} is not {}", value, expected_value);
+    }
+
+    #[test]
+    fn product_raises_scores_to_weights() {
+        assert_close(create_calculator(FitnessAggregation::Product).aggregate_fitness_score(&[0.5, 0.5]), 0.0625);
+    }
+
+    #[test]
+    fn geometric_mean_normalizes_weights() {
+        assert_close(create_calculator(FitnessAggregation::GeometricMean).aggregate_fitness_score(&[0.5, 0.5]), 0.5);
+        assert_close(create_calculator(FitnessAggregation::GeometricMean).aggregate_fitness_score(&[1.0, 0.0625]), 0.125);
+    }
+
+    #[test]
+    fn sum_adds_weighted_scores() {
+        assert_close(create_calculator(FitnessAggregation::Sum).aggregate_fitness_score(&[0.5, 0.25]), 1.25);
+    }
+
+    #[test]
+    fn chebyshev_takes_the_worst_weighted_score() {
+        assert_close(create_calculator(FitnessAggregation::Chebyshev).aggregate_fitness_score(&[0.5, 0.1]), 0.3);
+    }
+
+    #[test]
+    fn negative_scores_do_not_give_nan() {
+        assert!(!create_calculator(FitnessAggregation::GeometricMean).aggregate_fitness_score(&[-1.0, 0.5]).is_nan());
+    }
+
+    #[test]
+    fn aggregation_names_round_trip() {
+        for aggregation in [FitnessAggregation::Product, FitnessAggregation::GeometricMean, FitnessAggregation::Sum, FitnessAggregation::Chebyshev]
+        {
+            assert_eq!(FitnessAggregation::from_name(aggregation.name()), Some(aggregation));
+        }
+
+        assert_eq!(FitnessAggregation::from_name("median"), None);
+    }
+
+    #[test]
+    fn target_multiplier_is_limited() {
+        let calculator = create_calculator(FitnessAggregation::Product);
+
+        assert_close(calculator.calc_target_mul(50.0, 100.0, false), MIN_TARGET_MULTIPLIER + (1.0 - MIN_TARGET_MULTIPLIER) * 0.5);
+        assert_close(calculator.calc_target_mul(200.0, 100.0, false), 1.0);
+        assert_close(calculator.calc_target_mul(0.0, 100.0, false), MIN_TARGET_MULTIPLIER);
+        assert_close(calculator.calc_target_mul(50.0, 100.0, true), 1.0);
+    }
+}
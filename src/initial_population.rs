@@ -0,0 +1,166 @@
+use std::sync::Arc;
+use rand::Rng;
+use rand::prelude::SliceRandom;
+use crate::dna::{Dna, DnaContext, DnaData, MASTERY_CHOICES_COUNT};
+use crate::repair::repair_dna;
+use crate::tree_graph::TreeGraph;
+
+const WARM_START_MAX_MUTATIONS_COUNT: usize = 3;
+
+// Shares of the first generation, they are taken relative to their sum
+#[derive(Clone, Copy, Debug)]
+pub struct InitialPopulationMix
+{
+    // Connected subtrees grown from class start, they need tree graph
+    pub random: f64,
+    // Mutations of build dna, they need warm start
+    pub seeded: f64,
+    pub empty: f64
+}
+
+// Remainder of population is filled with empty dnas, so populations of islands can be joined one after another
+pub fn create_initial_population(mix: &InitialPopulationMix,
+                                 population_size: usize,
+                                 seed_dna_data: Option<DnaData>,
+                                 empty_dna_data: &DnaData,
+                                 dna_context: &Arc<DnaContext>) -> Vec<Dna>
+{
+    let shares_sum = mix.random + mix.seeded + mix.empty;
+
+    let get_count = |share: f64| (population_size as f64 * share / shares_sum).round() as usize;
+
+    let mut population = Vec::with_capacity(population_size);
+
+    if let Some(seed_dna_data) = seed_dna_data
+    {
+        population.extend(create_seeded_dnas(seed_dna_data, get_count(mix.seeded), dna_context));
+    }
+
+    if let Some(tree_graph) = &dna_context.tree_graph
+    {
+        let random_count = get_count(mix.random).min(population_size - population.len());
+
+        for _ in 0..random_count
+        {
+            population.push(create_random_subtree_dna(empty_dna_data, tree_graph, dna_context));
+        }
+    }
+
+    while population.len() < population_size
+    {
+        population.push(Dna::new_with_context(empty_dna_data.clone(), dna_context.clone()));
+    }
+
+    population
+}
+
+// Build dna goes first, it is only trimmed when build has more nodes than budget of solve
+fn create_seeded_dnas(seed_dna_data: DnaData, count: usize, dna_context: &Arc<DnaContext>) -> Vec<Dna>
+{
+    let mut dnas = Vec::with_capacity(count);
+
+    let mut seed_dna = Dna::new_with_context(seed_dna_data, dna_context.clone());
+
+    repair_dna(&mut seed_dna);
+
+    if count > 0
+    {
+        dnas.push(seed_dna.clone());
+    }
+
+    while dnas.len() < count
+    {
+        let mut dna = seed_dna.clone();
+
+        let mutations_count = dna_context.rng.lock().unwrap().gen_range(1..=WARM_START_MAX_MUTATIONS_COUNT);
+
+        for _ in 0..mutations_count
+        {
+            dna.mutate();
+        }
+
+        dnas.push(dna);
+    }
+
+    dnas
+}
+
+// Adds random frontier nodes one by one until nodes budget is spent, so the tree stays connected.
+// Every mastery gets its effects in random order
+fn create_random_subtree_dna(empty_dna_data: &DnaData, tree_graph: &TreeGraph, dna_context: &Arc<DnaContext>) -> Dna
+{
+    let mut dna = Dna::new_with_context(empty_dna_data.clone(), dna_context.clone());
+
+    let mut rng = dna_context.rng.lock().unwrap();
+
+    let mut selected_count = dna.body_nodes.iter().filter(|nucl| **nucl == 1).count();
+
+    let mut is_frontier = vec![false; dna.body_nodes.len()];
+
+    let mut frontier_indexes: Vec<usize> = tree_graph.get_frontier_indexes(&dna.body_nodes)
+        .into_iter()
+        .filter(|node_index| !dna_context.forbidden_node_indexes.contains(node_index))
+        .collect();
+
+    for node_index in &frontier_indexes
+    {
+        is_frontier[*node_index] = true;
+    }
+
+    while selected_count < dna.max_count_nodes && !frontier_indexes.is_empty()
+    {
+        let node_index = frontier_indexes.swap_remove(rng.gen_range(0..frontier_indexes.len()));
+
+        dna.body_nodes[node_index] = 1;
+        selected_count += 1;
+
+        for linked_index in &tree_graph.linked_indexes[node_index]
+        {
+            if dna.body_nodes[*linked_index] == 0
+                && !is_frontier[*linked_index]
+                && !tree_graph.root_indexes.contains(linked_index)
+                && !dna_context.forbidden_node_indexes.contains(linked_index)
+            {
+                is_frontier[*linked_index] = true;
+                frontier_indexes.push(*linked_index);
+            }
+        }
+    }
+
+    for mastery_index in 0..dna.get_masteries_count().min(tree_graph.mastery_effects_counts.len())
+    {
+        let mut effect_indexes: Vec<usize> = (0..tree_graph.get_mastery_effects_count(mastery_index)).collect();
+
+        effect_indexes.shuffle(&mut *rng);
+        effect_indexes.truncate(MASTERY_CHOICES_COUNT);
+
+        dna.set_mastery_effect_indexes(mastery_index, &effect_indexes);
+    }
+
+    dna
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_dna_over_budget_is_trimmed() {
+        let mut seed_dna_data = DnaData::new(8, 0, 0, 3);
+
+        seed_dna_data.body_nodes[1..6].fill(1);
+
+        let mix = InitialPopulationMix {
+            random: 0.0,
+            seeded: 1.0,
+            empty: 0.0
+        };
+
+        let population = create_initial_population(&mix, 1, Some(seed_dna_data), &DnaData::new(8, 0, 0, 3), &Arc::new(DnaContext::default()));
+
+        let selected_indexes: Vec<usize> = (0..8).filter(|node_index| population[0].body_nodes[*node_index] == 1).collect();
+
+        assert_eq!(selected_indexes.len(), 3);
+        assert!(selected_indexes.iter().all(|node_index| (1..6).contains(node_index)));
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::{Arc, Mutex};
+use crate::dna::Dna;
+use crate::solve_options::OptimizerType;
+
+// Optimizer settings of one population, every island has its own
+#[derive(Clone, Debug)]
+pub struct PopulationParameters
+{
+    pub optimizer_type: OptimizerType,
+    pub population_size: usize,
+    pub mutation_odds: f64,
+    pub crossover_odds: f64,
+    pub elites_count: usize,
+    pub tournament_size: usize
+}
+
+// Islands are joined in a ring: each island sends its best dnas to the next one
+pub struct Migration
+{
+    pub interval: usize,
+    pub rate: usize,
+    // Best dnas of the last migration of every island, they wait for the next island
+    emigrants: Mutex<Vec<Vec<Dna>>>
+}
+
+impl Migration
+{
+    pub fn new(islands_count: usize, interval: usize, rate: usize) -> Migration
+    {
+        Migration {
+            interval,
+            rate,
+            emigrants: Mutex::new(vec![vec![]; islands_count])
+        }
+    }
+
+    // Immigrants keep their scores, they are evaluated with the same targets
+    pub fn exchange(&self, island_index: usize, candidates: &mut [&mut Dna])
+    {
+        let mut candidate_indexes: Vec<usize> = (0..candidates.len()).collect();
+
+        candidate_indexes.sort_by(|a, b| candidates[*b].fitness_score.total_cmp(&candidates[*a].fitness_score));
+
+        let immigrants =
+            {
+                let mut emigrants = self.emigrants.lock().unwrap();
+
+                let previous_island_index = (island_index + emigrants.len() - 1) % emigrants.len();
+
+                let immigrants = std::mem::take(&mut emigrants[previous_island_index]);
+
+                emigrants[island_index] = candidate_indexes
+                    .iter()
+                    .take(self.rate)
+                    .map(|candidate_index| candidates[*candidate_index].clone())
+                    .collect();
+
+                immigrants
+            };
+
+        // Worst candidates give their places. Immigrants take context of this island, so they mutate with its generator
+        for (mut immigrant, candidate_index) in immigrants.into_iter().zip(candidate_indexes.iter().rev())
+        {
+            immigrant.context = candidates[*candidate_index].context.clone();
+
+            *candidates[*candidate_index] = immigrant;
+        }
+    }
+}
+
+pub struct Island
+{
+    pub index: usize,
+    pub migration: Arc<Migration>
+}
@@ -9,4 +9,19 @@ mod dna_cache_fitness;
 mod user_target;
 mod fitness_function_calculator;
 mod auto_targets;
+mod pareto;
+mod checkpoint;
+mod solve_options;
+mod nsga2_optimizer;
+mod elitist_optimizer;
+mod steady_state_optimizer;
+mod tree_graph;
+mod local_search;
+mod stop_criteria;
+mod stat_cache;
+mod repair;
+mod initial_population;
+mod diversity;
+mod islands;
+mod constraints;
 pub mod target;
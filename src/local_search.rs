@@ -0,0 +1,222 @@
+use rand::prelude::SliceRandom;
+use sss_moo::SolutionsRuntimeProcessor;
+use crate::constraints::is_better_dna;
+use crate::dna::Dna;
+use crate::tree_graph::TreeGraph;
+
+// Every neighbour costs an evaluation, larger neighbourhoods are sampled
+const MAX_NEIGHBOURS_COUNT: usize = 64;
+
+// Greedy hill climbing: every step evaluates neighbours of the current dna and moves to the best of them,
+// it ends when no neighbour is better or when is_interrupted says so
+pub fn polish_dna(runtime_processor: &mut dyn SolutionsRuntimeProcessor<Dna>,
+                  dna: &Dna,
+                  max_steps_count: usize,
+                  is_interrupted: &dyn Fn() -> bool) -> Dna
+{
+    let mut best_dna = dna.clone();
+
+    let tree_graph =
+        match &dna.context.tree_graph {
+            None => return best_dna,
+            Some(tree_graph) => tree_graph.clone()
+        };
+
+    for _ in 0..max_steps_count
+    {
+        if is_interrupted()
+        {
+            break;
+        }
+
+        let mut neighbour_moves = create_neighbour_moves(&best_dna, &tree_graph);
+
+        if neighbour_moves.is_empty()
+        {
+            break;
+        }
+
+        if neighbour_moves.len() > MAX_NEIGHBOURS_COUNT
+        {
+            let mut rng = best_dna.context.rng.lock().unwrap();
+
+            neighbour_moves.shuffle(&mut *rng);
+            neighbour_moves.truncate(MAX_NEIGHBOURS_COUNT);
+        }
+
+        let mut neighbours: Vec<Dna> = neighbour_moves
+            .iter()
+            .map(|neighbour_move| create_neighbour(&best_dna, neighbour_move))
+            .collect();
+
+        runtime_processor.new_candidates(neighbours.iter_mut().collect());
+
+        let best_neighbour = neighbours
+            .into_iter()
+            .reduce(|best_neighbour, neighbour| if is_better_dna(&neighbour, &best_neighbour) { neighbour } else { best_neighbour });
+
+        match best_neighbour {
+            Some(best_neighbour) if is_better_dna(&best_neighbour, &best_dna) => best_dna = best_neighbour,
+            _ => break
+        }
+    }
+
+    best_dna
+}
+
+// Swaps make neighbourhood large, so moves are cheap to list and only sampled ones become dnas
+enum NeighbourMove
+{
+    RemoveNode(usize),
+    AddNode(usize),
+    SwapNodes { removed_node_index: usize, added_node_index: usize },
+    ChangeMasteryEffect { mastery_index: usize, position: usize, effect_index: usize }
+}
+
+// Dnas which differ by one removed node, one added frontier node, one node swapped for a frontier node
+// or one changed mastery effect choice. Nodes are not added to dna which has spent its nodes budget
+fn create_neighbour_moves(dna: &Dna, tree_graph: &TreeGraph) -> Vec<NeighbourMove>
+{
+    let mut neighbour_moves = Vec::new();
+
+    let removable_node_indexes: Vec<usize> = dna.body_nodes
+        .iter()
+        .enumerate()
+        .filter(|(node_index, nucl)| **nucl == 1 && !dna.context.locked_node_indexes.contains(node_index))
+        .map(|(node_index, _)| node_index)
+        .collect();
+
+    let addable_node_indexes: Vec<usize> = tree_graph.get_frontier_indexes(&dna.body_nodes)
+        .into_iter()
+        .filter(|node_index| !dna.context.forbidden_node_indexes.contains(node_index))
+        .collect();
+
+    let selected_nodes_count = dna.body_nodes.iter().filter(|nucl| **nucl == 1).count();
+
+    neighbour_moves.extend(removable_node_indexes.iter().map(|node_index| NeighbourMove::RemoveNode(*node_index)));
+
+    if selected_nodes_count < dna.max_count_nodes
+    {
+        neighbour_moves.extend(addable_node_indexes.iter().map(|node_index| NeighbourMove::AddNode(*node_index)));
+    }
+
+    for removed_node_index in removable_node_indexes.iter()
+    {
+        for added_node_index in addable_node_indexes.iter()
+        {
+            neighbour_moves.push(NeighbourMove::SwapNodes {
+                removed_node_index: *removed_node_index,
+                added_node_index: *added_node_index
+            });
+        }
+    }
+
+    for mastery_index in 0..tree_graph.mastery_effects_counts.len().min(dna.get_masteries_count())
+    {
+        let effect_indexes = dna.get_mastery_effect_indexes(mastery_index);
+
+        for position in 0..effect_indexes.len()
+        {
+            for effect_index in 0..tree_graph.get_mastery_effects_count(mastery_index)
+            {
+                if !effect_indexes.contains(&effect_index)
+                {
+                    neighbour_moves.push(NeighbourMove::ChangeMasteryEffect { mastery_index, position, effect_index });
+                }
+            }
+        }
+    }
+
+    neighbour_moves
+}
+
+fn create_neighbour(dna: &Dna, neighbour_move: &NeighbourMove) -> Dna
+{
+    let mut neighbour = dna.clone();
+
+    match neighbour_move {
+        NeighbourMove::RemoveNode(node_index) => neighbour.body_nodes[*node_index] = 0,
+        NeighbourMove::AddNode(node_index) => neighbour.body_nodes[*node_index] = 1,
+        NeighbourMove::SwapNodes { removed_node_index, added_node_index } => {
+            neighbour.body_nodes[*removed_node_index] = 0;
+            neighbour.body_nodes[*added_node_index] = 1;
+        },
+        NeighbourMove::ChangeMasteryEffect { mastery_index, position, effect_index } => {
+            let mut effect_indexes = dna.get_mastery_effect_indexes(*mastery_index);
+
+            effect_indexes[*position] = *effect_index;
+
+            neighbour.set_mastery_effect_indexes(*mastery_index, &effect_indexes);
+        }
+    }
+
+    neighbour.reset_fitness_scores();
+
+    neighbour
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::dna::{DnaContext, DnaData};
+    use super::*;
+
+    // Root 0 - 1 - 2 - 3, and 1 - 4, node 2 is locked
+    fn create_dna(max_count_nodes: usize) -> (Dna, TreeGraph)
+    {
+        let tree_graph = TreeGraph {
+            linked_indexes: vec![vec![1], vec![0, 2, 4], vec![1, 3], vec![2], vec![1]],
+            root_indexes: vec![0],
+            mastery_effects_counts: vec![]
+        };
+
+        let context = DnaContext {
+            locked_node_indexes: vec![2],
+            ..DnaContext::default()
+        };
+
+        let mut dna = Dna::new_with_context(DnaData::new(5, 0, 0, max_count_nodes), Arc::new(context));
+
+        dna.body_nodes[1] = 1;
+        dna.body_nodes[2] = 1;
+
+        (dna, tree_graph)
+    }
+
+    fn get_selected_indexes(dna: &Dna) -> Vec<usize>
+    {
+        (0..dna.body_nodes.len()).filter(|node_index| dna.body_nodes[*node_index] == 1).collect()
+    }
+
+    fn get_neighbours(dna: &Dna, tree_graph: &TreeGraph) -> Vec<Vec<usize>>
+    {
+        create_neighbour_moves(dna, tree_graph)
+            .iter()
+            .map(|neighbour_move| get_selected_indexes(&create_neighbour(dna, neighbour_move)))
+            .collect()
+    }
+
+    #[test]
+    fn neighbours_remove_add_and_swap_unlocked_nodes() {
+        let (dna, tree_graph) = create_dna(3);
+
+        assert_eq!(get_neighbours(&dna, &tree_graph), vec![
+            vec![2],
+            vec![1, 2, 3],
+            vec![1, 2, 4],
+            vec![2, 3],
+            vec![2, 4]
+        ]);
+    }
+
+    #[test]
+    fn nodes_are_not_added_over_budget() {
+        let (dna, tree_graph) = create_dna(2);
+
+        assert_eq!(get_neighbours(&dna, &tree_graph), vec![
+            vec![2],
+            vec![2, 3],
+            vec![2, 4]
+        ]);
+    }
+}
@@ -1,6 +1,8 @@
 use std::{fs, panic};
 use mlua::Lua;
 use mlua::prelude::{LuaResult, LuaTable};
+use crate::auto_targets::lua_get_auto_targets;
+use crate::dna::lua_deserialize_dna;
 use crate::dna_encoder::{lua_create_dna_encoder};
 use crate::pob_solver::{create_genetic_solver};
 
@@ -11,6 +13,10 @@ fn path_of_building_genetic_solver(lua: &Lua) -> LuaResult<LuaTable> {
 
     exports.set("CreateDnaEncoder", lua.create_function(lua_create_dna_encoder)?)?;
 
+    exports.set("DeserializeDna", lua.create_function(lua_deserialize_dna)?)?;
+
+    exports.set("GetAutoTargets", lua.create_function(lua_get_auto_targets)?)?;
+
     let orig_hook = panic::take_hook();
     panic::set_hook(Box::new(move |panic_info| {
         // invoke the default handler and exit the process
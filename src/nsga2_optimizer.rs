@@ -0,0 +1,271 @@
+use std::cmp::Ordering;
+
+use rand::prelude::{SliceRandom, StdRng};
+use rand::{Rng, SeedableRng};
+use sss_moo::evaluator::Evaluator;
+use sss_moo::optimizers::Optimizer;
+use sss_moo::{Meta, Objective, Ratio, Solution, SolutionsRuntimeProcessor};
+
+use crate::pareto::{cmp_objective_values, non_dominated_sort};
+
+#[derive(Clone)]
+struct Candidate<S: Solution> {
+    sol: S,
+    front: usize,
+    distance: f64,
+}
+
+// NSGA-II from sss_moo, but every random choice is taken from a seeded generator
+pub struct Nsga2Optimizer<'a, S: Solution> {
+    meta: Box<dyn Meta<'a, S> + 'a>,
+    rng: StdRng,
+    best_solutions: Vec<(Vec<f64>, S)>,
+}
+
+impl<'a, S> Optimizer<S> for Nsga2Optimizer<'a, S>
+    where
+        S: Solution,
+{
+    fn name(&self) -> &str {
+        "NSGA-II"
+    }
+
+    fn optimize(&mut self, eval: &mut Box<dyn Evaluator>, runtime_solutions_processor: &mut Box<dyn SolutionsRuntimeProcessor<S>>) {
+        let pop_size = self.meta.population_size();
+        let crossover_odds = self.meta.crossover_odds();
+        let mutation_odds = self.meta.mutation_odds();
+
+        let mut pop: Vec<_> = (0..pop_size)
+            .map(|_| {
+                Candidate {
+                    sol: self.meta.random_solution(),
+                    front: 0,
+                    distance: 0.0,
+                }
+            })
+            .collect();
+
+        runtime_solutions_processor.new_candidates(
+            pop
+                .iter_mut()
+                .map(|candidate| &mut candidate.sol)
+                .collect()
+        );
+
+        let mut parent_pop = self.sort(pop);
+
+        for iter in 0.. {
+            if runtime_solutions_processor.needs_early_stop()
+            {
+                break;
+            }
+
+            runtime_solutions_processor.iteration_num(iter);
+
+            runtime_solutions_processor.iter_solutions(
+                parent_pop.iter_mut()
+                    .map(|child| &mut child.sol)
+                    .collect()
+            );
+
+            // Processor can replace candidates, for example by migration or duplicates elimination
+            parent_pop = self.sort(parent_pop);
+
+            self.best_solutions.clear();
+            parent_pop
+                .iter()
+                .take_while(|c| c.front == 0)
+                .for_each(|c| {
+                    let vals: Vec<f64> = self.values(&c.sol);
+
+                    self.best_solutions.push((vals, c.sol.clone()));
+                });
+
+            // Check if there's a good-enough solution already
+            if parent_pop
+                .iter()
+                .any(|c| {
+                    self.meta
+                        .objectives()
+                        .iter()
+                        .all(|obj| obj.good_enough(self.value(&c.sol, obj)))
+                })
+            {
+                break;
+            }
+
+            if eval.can_terminate(iter, parent_pop.iter().map(|c| self.values(&c.sol)).collect())
+            {
+                break;
+            }
+
+            let mut child_pop: Vec<Candidate<S>> = Vec::with_capacity(pop_size);
+
+            while child_pop.len() < pop_size {
+                let p1 = parent_pop.choose(&mut self.rng).unwrap().clone();
+                let p2 = parent_pop.choose(&mut self.rng).unwrap().clone();
+                let p3 = parent_pop.choose(&mut self.rng).unwrap().clone();
+                let p4 = parent_pop.choose(&mut self.rng).unwrap().clone();
+
+                let mut c1 = self.tournament(p1, p2);
+                let mut c2 = self.tournament(p3, p4);
+
+                if self.odds(crossover_odds) {
+                    c1.sol.crossover(&mut c2.sol);
+                };
+
+                if self.odds(mutation_odds) {
+                    c1.sol.mutate();
+                };
+
+                if self.odds(mutation_odds) {
+                    c2.sol.mutate();
+                };
+
+                child_pop.push(c1);
+                child_pop.push(c2);
+            }
+
+            runtime_solutions_processor.new_candidates(
+                child_pop
+                    .iter_mut()
+                    .map(|child| &mut child.sol)
+                    .collect()
+            );
+
+            parent_pop.extend(child_pop);
+
+            parent_pop = self.sort(parent_pop);
+
+            parent_pop.truncate(pop_size)
+        }
+    }
+
+    fn best_solutions(&self) -> Vec<(Vec<f64>, S)> {
+        self.best_solutions.clone()
+    }
+}
+
+impl<'a, S> Nsga2Optimizer<'a, S>
+    where
+        S: Solution,
+{
+    pub fn new(meta: impl Meta<'a, S> + 'a, seed: u64) -> Self {
+        Nsga2Optimizer {
+            meta: Box::new(meta),
+            rng: StdRng::seed_from_u64(seed),
+            best_solutions: Vec::new(),
+        }
+    }
+
+    fn odds(&mut self, ratio: &Ratio) -> bool {
+        self.rng.gen_ratio(ratio.0, ratio.1)
+    }
+
+    fn tournament(&mut self, p1: Candidate<S>, p2: Candidate<S>) -> Candidate<S> {
+        if p1.front < p2.front {
+            p1
+        } else if p2.front < p1.front {
+            p2
+        } else if p1.distance > p2.distance {
+            p1
+        } else if p2.distance > p1.distance {
+            p2
+        } else if self.rng.gen_bool(0.5) {
+            p1
+        } else {
+            p2
+        }
+    }
+
+    fn sort(&self, pop: Vec<Candidate<S>>) -> Vec<Candidate<S>> {
+        let objs: Vec<Vec<f64>> = pop.iter()
+            .map(|p| self.values(&p.sol))
+            .collect();
+
+        let mut fronts: Vec<Candidate<S>> = Vec::with_capacity(pop.len());
+        for (fidx, f) in non_dominated_sort(&objs).into_iter().enumerate() {
+            for index in f {
+                fronts.push(Candidate {
+                    sol: pop[index].sol.clone(),
+                    front: fidx,
+                    distance: 0.0,
+                });
+            }
+        }
+
+        // Crowding distance
+        let last_front_index = fronts.len() - 1;
+
+        for obj in self.meta.objectives() {
+            fronts.sort_by(|a, b| {
+                let a_obj = self.value(&a.sol, obj);
+                let b_obj = self.value(&b.sol, obj);
+
+                cmp_objective_values(a_obj, b_obj)
+            });
+
+            let min = self.value(&fronts[0].sol, obj);
+            let max = self.value(&fronts[last_front_index].sol, obj);
+
+            let diff = max - min;
+
+            fronts[0].distance = f64::MAX;
+            fronts[last_front_index].distance = f64::MAX;
+
+            if diff != 0. && !diff.is_nan()
+            {
+                for i in 1..last_front_index {
+                    if fronts[i].distance != f64::MAX {
+                        let next_value = self.value(&fronts[i + 1].sol, obj);
+                        let prev_value = self.value(&fronts[i - 1].sol, obj);
+
+                        if next_value.is_infinite()
+                        {
+                            if prev_value.is_infinite()
+                            {
+                                continue;
+                            }
+
+                            fronts[i].distance = f64::MAX;
+                            continue;
+                        }
+
+                        fronts[i].distance += (next_value - prev_value) / diff;
+                    }
+                }
+            }
+        }
+
+        // First sort by front and then by distance, NaN distance is ordered as the smallest one
+        let distance_or_smallest = |distance: f64| if distance.is_nan() { f64::NEG_INFINITY } else { distance };
+
+        fronts.sort_by(|a, b| {
+            if a.front != b.front {
+                a.front.cmp(&b.front)
+            } else if a.distance != b.distance {
+                distance_or_smallest(b.distance).total_cmp(&distance_or_smallest(a.distance))
+            } else {
+                Ordering::Equal
+            }
+        });
+
+        fronts
+    }
+
+    #[allow(clippy::borrowed_box)]
+    fn value(&self, s: &S, obj: &Box<dyn Objective<S> + 'a>) -> f64 {
+        self.meta
+            .constraints()
+            .iter()
+            .fold(obj.value(s), |acc, cons| cons.value(s, acc))
+    }
+
+    fn values(&self, s: &S) -> Vec<f64> {
+        self.meta
+            .objectives()
+            .iter()
+            .map(|obj| self.value(s, obj))
+            .collect()
+    }
+}
@@ -0,0 +1,156 @@
+use std::cmp::Ordering;
+
+// Objective values are minimized, same as in sss_moo, NaN is ordered as the worst value
+pub fn cmp_objective_values(value1: f64, value2: f64) -> Ordering
+{
+    let worst_if_nan = |value: f64| if value.is_nan() { f64::INFINITY } else { value };
+
+    worst_if_nan(value1).total_cmp(&worst_if_nan(value2))
+}
+
+pub fn dominates(values1: &[f64], values2: &[f64]) -> bool
+{
+    let mut is_better_in_any = false;
+
+    for (value1, value2) in values1.iter().zip(values2.iter())
+    {
+        match cmp_objective_values(*value1, *value2) {
+            Ordering::Less => is_better_in_any = true,
+            Ordering::Greater => return false,
+            Ordering::Equal => {}
+        }
+    }
+
+    is_better_in_any
+}
+
+pub fn non_dominated_indexes(values: &[Vec<f64>]) -> Vec<usize>
+{
+    let mut indexes = Vec::new();
+
+    for (index, candidate_values) in values.iter().enumerate()
+    {
+        let is_dominated = values
+            .iter()
+            .any(|other_values| dominates(other_values, candidate_values));
+
+        if !is_dominated
+        {
+            indexes.push(index);
+        }
+    }
+
+    indexes
+}
+
+// Efficient non-dominated sort, returns indexes of values grouped by fronts
+pub fn non_dominated_sort(values: &[Vec<f64>]) -> Vec<Vec<usize>>
+{
+    let mut indexes: Vec<usize> = (0..values.len()).collect();
+
+    indexes.sort_by(|a, b| {
+        for (value_a, value_b) in values[*a].iter().zip(values[*b].iter())
+        {
+            match cmp_objective_values(*value_a, *value_b) {
+                Ordering::Equal => {},
+                ordering => return ordering
+            }
+        }
+
+        Ordering::Equal
+    });
+
+    let mut fronts: Vec<Vec<usize>> = vec![];
+
+    for index in indexes
+    {
+        let front_index = fronts
+            .iter()
+            .position(|front| {
+                !front
+                    .iter()
+                    .rev()
+                    .any(|front_index| dominates(&values[*front_index], &values[index]))
+            });
+
+        match front_index {
+            Some(front_index) => fronts[front_index].push(index),
+            None => fronts.push(vec![index])
+        }
+    }
+
+    fronts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dominance_needs_better_value_and_no_worse_ones() {
+        assert!(dominates(&[1.0, 2.0], &[1.0, 3.0]));
+        assert!(!dominates(&[1.0, 2.0], &[1.0, 2.0]));
+        assert!(!dominates(&[0.0, 3.0], &[1.0, 2.0]));
+    }
+
+    #[test]
+    fn nan_is_the_worst_value() {
+        assert!(dominates(&[1.0, 2.0], &[1.0, f64::NAN]));
+        assert!(!dominates(&[1.0, f64::NAN], &[1.0, 2.0]));
+        assert_eq!(non_dominated_sort(&[vec![f64::NAN], vec![1.0]]), vec![vec![1], vec![0]]);
+    }
+
+    #[test]
+    fn non_dominated_indexes_keep_equal_candidates() {
+        let values = vec![
+            vec![1.0, 3.0],
+            vec![2.0, 2.0],
+            vec![2.0, 3.0],
+            vec![1.0, 3.0],
+            vec![3.0, 1.0]
+        ];
+
+        assert_eq!(non_dominated_indexes(&values), vec![0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn non_dominated_sort_groups_fronts() {
+        let values = vec![
+            vec![3.0, 3.0],
+            vec![1.0, 2.0],
+            vec![2.0, 1.0],
+            vec![2.0, 2.0],
+            vec![4.0, 4.0]
+        ];
+
+        let mut fronts = non_dominated_sort(&values);
+
+        for front in fronts.iter_mut()
+        {
+            front.sort_unstable();
+        }
+
+        assert_eq!(fronts, vec![vec![1, 2], vec![3], vec![0], vec![4]]);
+    }
+
+    #[test]
+    fn non_dominated_sort_keeps_equal_candidates_in_one_front() {
+        let values = vec![
+            vec![1.0, 3.0],
+            vec![2.0, 2.0],
+            vec![2.0, 3.0],
+            vec![1.0, 3.0],
+            vec![3.0, 1.0]
+        ];
+
+        let mut fronts = non_dominated_sort(&values);
+
+        for front in fronts.iter_mut()
+        {
+            front.sort_unstable();
+        }
+
+        assert_eq!(fronts[0], non_dominated_indexes(&values));
+        assert_eq!(fronts, vec![vec![0, 1, 3, 4], vec![2]]);
+    }
+}
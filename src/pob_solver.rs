@@ -1,41 +1,133 @@
-use std::{env, thread};
+use std::{env, panic, thread};
+use std::any::Any;
 use std::fmt::{Debug, Formatter};
+use std::collections::{HashMap, HashSet};
+use std::panic::AssertUnwindSafe;
 use std::rc::Rc;
-use std::sync::{Arc, RwLock};
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::thread::{JoinHandle};
+use std::time::{Duration, Instant};
 
-use crossbeam::channel::{Receiver, Sender, unbounded};
+use crossbeam::channel::{Receiver, RecvTimeoutError, Sender, unbounded};
 use mlua::prelude::*;
 use mlua::{UserData, UserDataMethods};
 
-use rand::prelude::{ThreadRng};
-use rand::{thread_rng};
-use crate::auto_targets::{AutoTargetFromStatToStat, AutoTargetManaCost, AutoTargetManaRegen};
-
-use crate::dna::{Dna, DnaData, LuaDna};
+use rand::prelude::StdRng;
+use rand::{Rng, SeedableRng, thread_rng};
+use crate::auto_targets::create_auto_target;
+
+use crate::checkpoint::Checkpoint;
+use crate::dna::{Dna, DnaContext, DnaData, LuaDna};
+use crate::dna_cache_fitness::DnaCacheFitness;
+use crate::constraints::{get_constraint_target_indexes, is_better_dna, TargetsConstraint};
+use crate::fitness_function_calculator::{FitnessAggregation, FitnessFunctionCalculator};
+use crate::stat_cache::{StatCache, StatCacheSettings};
+use crate::dna_encoder::{create_dna_encoder, read_build_tree, read_tree_version};
+use crate::elitist_optimizer::ElitistOptimizer;
+use crate::local_search::polish_dna;
+use crate::diversity::mean_hamming_distance;
+use crate::islands::{Island, Migration, PopulationParameters};
+use crate::nsga2_optimizer::Nsga2Optimizer;
+use crate::steady_state_optimizer::{SteadyStateOptimizer, SteadyStateProcessor};
+use crate::pareto::non_dominated_indexes;
 use sss_moo::{Constraint, Meta, Objective, Ratio, Solution, SolutionsRuntimeProcessor};
-use sss_moo::evaluator::{DefaultEvaluator, Evaluator};
-use sss_moo::optimizers::nsga2::NSGA2Optimizer;
+use sss_moo::evaluator::Evaluator;
 use sss_moo::optimizers::Optimizer;
 use crate::target::Target;
 use crate::user_target::{create_targets_from_tables};
-use crate::worker::worker_main;
+use crate::stop_criteria::{is_targets_met, SolveEvaluator, StopCriteria, StopReason};
+use crate::solve_options::{options_error, parse_solve_options, OptimizerType};
+use crate::worker::{panic_message, spawn_worker, WorkersStatus};
+use crate::initial_population::create_initial_population;
+
+const ODDS_RATIO_DENOMINATOR: u32 = 10000;
+
+const DUPLICATE_MUTATIONS_COUNT: usize = 5;
+
+// Dna is sent to another worker once before it is given the worst scores
+const MAX_DNA_EVALUATION_ATTEMPTS: usize = 2;
+
+const WORKERS_CHECK_INTERVAL: Duration = Duration::from_millis(500);
 
 pub struct DnaCommand {
-    pub dna: Option<Dna>
+    pub dna: Option<Dna>,
+    // Position of dna in candidates batch, results come back in any order
+    pub index: usize,
+    pub attempt_number: usize,
+    // Set by worker when dna cannot be evaluated
+    pub error: Option<String>,
+    // Channel of island which sent dna, solver channel is used when it is not set
+    pub result_channel: Option<Sender<Box<DnaCommand>>>
 }
 
 pub struct Session {
     pub number: usize,
     pub target_normal_nodes_count: usize,
     pub target_ascendancy_nodes_count: usize,
-    pub targets: Vec<Box<dyn Target>>
+    pub targets: Vec<Box<dyn Target>>,
+    pub fitness_aggregation: FitnessAggregation,
+    pub fitness_cache: Arc<DnaCacheFitness>,
+    pub stat_cache: Option<Arc<StatCache>>
+}
+
+#[derive(Clone)]
+pub struct SolveParameters {
+    pub stop_generations_eps: usize,
+    pub population_max_generation_size: usize,
+    pub tree_nodes_count: usize,
+    pub masteries_nodes_count: usize,
+    // Checkpoint is resumed only with build of the same tree version
+    pub tree_version: Option<String>,
+    pub target_normal_nodes_count: usize,
+    pub target_ascendancy_nodes_count: usize,
+    pub mutation_odds: f64,
+    pub crossover_odds: f64,
+    pub seed: u64,
+    pub stop_criteria: StopCriteria,
+    pub stat_cache_settings: Option<StatCacheSettings>,
+    pub optimizer_type: OptimizerType,
+    pub elites_count: usize,
+    pub tournament_size: usize,
+    pub is_local_search_after_solve: bool,
+    pub local_search_interval: Option<usize>,
+    pub local_search_max_steps: usize,
+    // Dnas decoded to the same tree as another dna of population are replaced by heavy mutations
+    pub is_eliminate_duplicates: bool,
+    // Empty when solve runs one population with parameters above
+    pub islands: Vec<PopulationParameters>,
+    pub migration_interval: usize,
+    pub migration_rate: usize,
+    pub fitness_aggregation: FitnessAggregation
+}
+
+impl SolveParameters {
+    pub fn get_population_parameters(&self) -> PopulationParameters {
+        PopulationParameters {
+            optimizer_type: self.optimizer_type,
+            population_size: self.population_max_generation_size,
+            mutation_odds: self.mutation_odds,
+            crossover_odds: self.crossover_odds,
+            elites_count: self.elites_count,
+            tournament_size: self.tournament_size
+        }
+    }
 }
 
 pub struct ProcessStatus {
     pub best_dna: Option<Dna>,
     pub best_dna_number: usize,
+    pub pareto_front: Vec<Dna>,
+    // Populations of all islands one after another
+    pub population: Vec<Dna>,
+    pub island_populations: Vec<Vec<Dna>>,
+    pub stop_reason: Option<StopReason>,
+    pub evaluations_count: usize,
+    // Mean Hamming distance between allocated node sets of population
+    pub population_diversity: f64,
+    pub replaced_duplicates_count: usize,
+    // Panic message of solver thread, see StopReason::SolverFailed
+    pub solver_error: Option<String>,
     pub is_progress: bool
 }
 
@@ -49,10 +141,12 @@ pub struct LuaGeneticSolver
 
     pub session: Arc<RwLock<Session>>,
     pub process_status: Arc<RwLock<ProcessStatus>>,
+    pub solve_parameters: Option<SolveParameters>,
 
     pub current_generation_number: Arc<AtomicU64>,
 
     pub workers_was_created: bool,
+    pub workers_status: Arc<WorkersStatus>,
 
     pub main_thread: Option<JoinHandle<()>>,
 
@@ -85,65 +179,364 @@ struct SolutionsRuntimeDnaProcessor
 {
     writer_dna_queue_channel: Sender<Box<DnaCommand>>,
     reader_dna_result_queue_channel: Receiver<Box<DnaCommand>>,
+    result_channel: Option<Sender<Box<DnaCommand>>>,
     process_status: Arc<RwLock<ProcessStatus>>,
     current_generation_number: Arc<AtomicU64>,
+    start_generation_number: usize,
     is_received_stop_request: Arc<AtomicBool>,
-    best_solution_fitness: f64
+    workers_status: Arc<WorkersStatus>,
+    objectives: Vec<Box<dyn Objective<Dna>>>,
+    stop_criteria: StopCriteria,
+    required_target_indexes: Vec<usize>,
+    start_time: Instant,
+    generation_number: usize,
+    island: Option<Island>,
+    local_search_interval: Option<usize>,
+    local_search_max_steps: usize,
+    is_eliminate_duplicates: bool,
+    // Candidates of steady state are sent one by one and come back by their own channel,
+    // so batches of new_candidates do not get them
+    writer_pending_result_channel: Sender<Box<DnaCommand>>,
+    reader_pending_result_channel: Receiver<Box<DnaCommand>>,
+    pending_dnas: HashMap<usize, Dna>,
+    next_pending_dna_index: usize
+}
+
+impl SolutionsRuntimeDnaProcessor
+{
+    // Best dna of generation is replaced by its polished version, so improvements go on to the next generations
+    fn polish_best_candidate(&mut self, candidates: &mut [&mut Dna])
+    {
+        let best_candidate_index =
+            match (0..candidates.len()).max_by(|a, b| candidates[*a].fitness_score.total_cmp(&candidates[*b].fitness_score)) {
+                None => return,
+                Some(best_candidate_index) => best_candidate_index
+            };
+
+        let is_interrupted = create_local_search_interruption(self.is_received_stop_request.clone(),
+                                                              self.process_status.clone(),
+                                                              self.start_time,
+                                                              &self.stop_criteria);
+
+        let local_search_max_steps = self.local_search_max_steps;
+
+        let polished_dna = polish_dna(self, candidates[best_candidate_index], local_search_max_steps, &is_interrupted);
+
+        *candidates[best_candidate_index] = polished_dna;
+    }
+
+    // The first dna of each decoded tree is kept, others are mutated heavily and evaluated again.
+    // Replacements are not checked again, so a generation costs at most one extra evaluation of each dna
+    fn replace_duplicates(&mut self, candidates: &mut [&mut Dna])
+    {
+        let mut decoded_tree_keys = HashSet::new();
+
+        let mut duplicates: Vec<&mut Dna> = candidates
+            .iter_mut()
+            .filter(|dna| matches!(dna.decoded_tree_key, Some(decoded_tree_key) if !decoded_tree_keys.insert(decoded_tree_key)))
+            .map(|dna| &mut **dna)
+            .collect();
+
+        if duplicates.is_empty()
+        {
+            return;
+        }
+
+        for dna in duplicates.iter_mut()
+        {
+            for _ in 0..DUPLICATE_MUTATIONS_COUNT
+            {
+                dna.mutate();
+            }
+        }
+
+        self.process_status.write().unwrap().replaced_duplicates_count += duplicates.len();
+
+        self.new_candidates(duplicates);
+    }
+
+    fn send_dna_command(&self, dna_command: DnaCommand)
+    {
+        // Receiver is owned by solver, so it is alive while genetic solve runs
+        self.writer_dna_queue_channel.send(Box::new(dna_command)).unwrap();
+    }
+
+    fn set_stop_reason(&self, stop_reason: StopReason)
+    {
+        let mut process_status = self.process_status.write().unwrap();
+
+        if process_status.stop_reason.is_none()
+        {
+            process_status.stop_reason = Some(stop_reason);
+        }
+    }
+}
+
+impl SteadyStateProcessor<Dna> for SolutionsRuntimeDnaProcessor
+{
+    fn send_candidate(&mut self, dna: Dna) {
+        let index = self.next_pending_dna_index;
+
+        self.next_pending_dna_index += 1;
+
+        self.send_dna_command(DnaCommand {
+            dna: Some(dna.clone()),
+            index,
+            attempt_number: 1,
+            error: None,
+            result_channel: Some(self.writer_pending_result_channel.clone())
+        });
+
+        self.pending_dnas.insert(index, dna);
+    }
+
+    fn receive_candidate(&mut self) -> Option<Dna> {
+        loop {
+            let mut dna_command =
+                match self.reader_pending_result_channel.recv_timeout(WORKERS_CHECK_INTERVAL) {
+                    Ok(dna_command) => dna_command,
+                    Err(RecvTimeoutError::Timeout) if self.workers_status.alive_workers_count.load(Ordering::SeqCst) > 0 => continue,
+                    Err(_) => {
+                        self.set_stop_reason(StopReason::WorkersFailed);
+
+                        return None;
+                    }
+                };
+
+            if dna_command.error.is_some() && dna_command.attempt_number < MAX_DNA_EVALUATION_ATTEMPTS
+            {
+                dna_command.attempt_number += 1;
+                dna_command.error = None;
+
+                self.send_dna_command(*dna_command);
+
+                continue;
+            }
+
+            let mut dna = self.pending_dnas.remove(&dna_command.index)?;
+
+            match (dna_command.error.take(), dna_command.dna.take()) {
+                (None, Some(dna_from_command)) => dna = dna_from_command,
+                _ => dna.reset_fitness_scores()
+            }
+
+            self.process_status.write().unwrap().evaluations_count += 1;
+
+            return Some(dna);
+        }
+    }
+
+    fn parallel_evaluations_count(&self) -> usize {
+        self.workers_status.alive_workers_count.load(Ordering::SeqCst)
+    }
 }
 
 impl SolutionsRuntimeProcessor<Dna> for SolutionsRuntimeDnaProcessor
 {
     fn new_candidates(&mut self, mut dnas: Vec<&mut Dna>) {
-        for dna in dnas.iter_mut()
+        for (index, dna) in dnas.iter().enumerate()
         {
-            let mut new_dna = Dna::new(DnaData::new(1, 1, 1, 1));
+            self.send_dna_command(DnaCommand {
+                dna: Some((*dna).clone()),
+                index,
+                attempt_number: 1,
+                error: None,
+                result_channel: self.result_channel.clone()
+            });
+        }
 
-            std::mem::swap(&mut new_dna, *dna);
+        let mut is_evaluated_dnas = vec![false; dnas.len()];
+        let mut pending_dnas_count = dnas.len();
 
-            let dna_command = DnaCommand {
-                dna: Some(new_dna)
-            };
+        while pending_dnas_count > 0
+        {
+            let mut dna_command =
+                match self.reader_dna_result_queue_channel.recv_timeout(WORKERS_CHECK_INTERVAL) {
+                    Ok(dna_command) => dna_command,
+                    Err(RecvTimeoutError::Timeout) if self.workers_status.alive_workers_count.load(Ordering::SeqCst) > 0 => continue,
+                    Err(_) => {
+                        // Nobody is left to evaluate dnas, solve stops and last worker error stays for Lua
+                        self.set_stop_reason(StopReason::WorkersFailed);
+
+                        for (dna, is_evaluated) in dnas.iter_mut().zip(is_evaluated_dnas.iter())
+                        {
+                            if !is_evaluated
+                            {
+                                dna.reset_fitness_scores();
+                            }
+                        }
+
+                        break;
+                    }
+                };
+
+            if dna_command.error.is_some() && dna_command.attempt_number < MAX_DNA_EVALUATION_ATTEMPTS
+            {
+                dna_command.attempt_number += 1;
+                dna_command.error = None;
+
+                self.send_dna_command(*dna_command);
+
+                continue;
+            }
+
+            let index = dna_command.index;
+
+            match (dna_command.error.take(), dna_command.dna.take()) {
+                (None, Some(mut dna_from_command)) => std::mem::swap(&mut dna_from_command, dnas[index]),
+                // Dna which cannot be evaluated gets the worst scores
+                _ => dnas[index].reset_fitness_scores()
+            }
 
-            self.writer_dna_queue_channel.send(Box::new(dna_command)).unwrap();
+            is_evaluated_dnas[index] = true;
+            pending_dnas_count -= 1;
         }
 
-        for dna in dnas
+        // Islands share the budget of evaluations
+        self.process_status.write().unwrap().evaluations_count += dnas.len();
+    }
+
+    fn iter_solutions(&mut self, mut candidates: Vec<&mut Dna>) {
+        if let Some(island) = &self.island
         {
-            let mut dna_command = self.reader_dna_result_queue_channel.recv().expect("Cannot receive dna result signal");
+            if self.generation_number > 0 && self.generation_number.is_multiple_of(island.migration.interval)
+            {
+                island.migration.exchange(island.index, &mut candidates);
+            }
+        }
 
-            let mut dna_from_command = dna_command.dna.take().unwrap();
+        if self.is_eliminate_duplicates
+        {
+            self.replace_duplicates(&mut candidates);
+        }
 
-            std::mem::swap(&mut dna_from_command, dna);
+        if let Some(local_search_interval) = self.local_search_interval
+        {
+            if self.generation_number > 0 && self.generation_number.is_multiple_of(local_search_interval)
+            {
+                self.polish_best_candidate(&mut candidates);
+            }
         }
-    }
 
-    fn iter_solutions(&mut self, candidates: Vec<&mut Dna>) {
+        let population: Vec<Dna> = candidates
+            .iter()
+            .map(|dna| (*dna).clone())
+            .collect();
+
+        // Front and diversity are taken over all islands
+        let whole_population =
+            {
+                let mut process_status = self.process_status.write().unwrap();
+
+                match &self.island {
+                    None => process_status.population = population,
+                    Some(island) => {
+                        process_status.island_populations[island.index] = population;
+                        process_status.population = process_status.island_populations.concat();
+                    }
+                }
+
+                process_status.population.clone()
+            };
+
+        // Computed without lock, other islands and Lua readers do not wait for it
+        let population_values: Vec<Vec<f64>> = whole_population
+            .iter()
+            .map(|dna| {
+                self.objectives
+                    .iter()
+                    .map(|objective| TargetsConstraint{}.value(dna, objective.value(dna)))
+                    .collect()
+            })
+            .collect();
+
+        let pareto_front: Vec<Dna> = non_dominated_indexes(&population_values)
+            .into_iter()
+            .map(|index| whole_population[index].clone())
+            .collect();
+
+        let allocated_node_sets: Vec<&[i64]> = whole_population
+            .iter()
+            .map(|dna| dna.allocated_node_ids.as_slice())
+            .collect();
+
+        let population_diversity = mean_hamming_distance(&allocated_node_sets);
+
+        {
+            let mut process_status = self.process_status.write().unwrap();
+
+            process_status.pareto_front = pareto_front;
+            process_status.population_diversity = population_diversity;
+        }
+
+        let is_targets_met = self.stop_criteria.is_stop_when_targets_met
+            && candidates
+                .iter()
+                .any(|dna| is_targets_met(&dna.fitness_score_targets, &self.required_target_indexes));
+
         for dna in candidates
         {
-            if dna.fitness_score > self.best_solution_fitness
+            let mut process_status = self.process_status.write().unwrap();
+
+            // Best dna is common for all islands
+            let is_best_dna = process_status.best_dna
+                .as_ref()
+                .is_none_or(|best_dna| is_better_dna(dna, best_dna));
+
+            if is_best_dna
             {
-                {
-                    let mut process_status = self.process_status.write().unwrap();
+                process_status.best_dna = Some((*dna).clone());
+                process_status.best_dna_number += 1;
+            }
+        }
 
-                    process_status.best_dna = Some(
-                        Dna {
-                            reference: dna.reference.clone()
-                        }
-                    );
-                    process_status.best_dna_number += 1;
-                }
+        if is_targets_met
+        {
+            self.set_stop_reason(StopReason::TargetsMet);
+        }
 
-                self.best_solution_fitness = dna.fitness_score;
+        if let Some(max_generations) = self.stop_criteria.max_generations
+        {
+            if self.generation_number + 1 >= max_generations
+            {
+                self.set_stop_reason(StopReason::GenerationsLimit);
             }
         }
     }
 
     fn iteration_num(&mut self, num: usize) {
-        self.current_generation_number.store(num as u64, Ordering::SeqCst);
+        self.generation_number = self.start_generation_number + num;
+
+        // Islands go at their own pace, the first one gives generation number of solve
+        if self.island.as_ref().is_none_or(|island| island.index == 0)
+        {
+            self.current_generation_number.store(self.generation_number as u64, Ordering::SeqCst);
+        }
     }
 
     fn needs_early_stop(&mut self) -> bool {
-        self.is_received_stop_request.load(Ordering::SeqCst)
+        if self.is_received_stop_request.load(Ordering::SeqCst)
+        {
+            self.set_stop_reason(StopReason::StopRequest);
+        }
+
+        if let Some(time_limit) = self.stop_criteria.time_limit
+        {
+            if self.start_time.elapsed() >= time_limit
+            {
+                self.set_stop_reason(StopReason::TimeLimit);
+            }
+        }
+
+        if let Some(max_evaluations) = self.stop_criteria.max_evaluations
+        {
+            if self.process_status.read().unwrap().evaluations_count >= max_evaluations
+            {
+                self.set_stop_reason(StopReason::EvaluationsLimit);
+            }
+        }
+
+        self.process_status.read().unwrap().stop_reason.is_some()
     }
 }
 
@@ -176,13 +569,32 @@ impl<'a> Objective<Dna> for TargetObjective {
     }
 }
 
+fn create_objectives(targets_count: usize) -> Vec<Box<dyn Objective<Dna>>>
+{
+    let mut objectives: Vec<Box<dyn Objective<Dna>>> = Vec::new();
+
+    for target_index in 0..targets_count
+    {
+        objectives.push(Box::new(TargetObjective {
+            target_index
+        }));
+    }
+
+    objectives.push(Box::new(FitnessScoreObjective{}));
+
+    objectives
+}
+
 struct Params<'a> {
     population_max_generation_size: usize,
+    crossover_odds: &'a Ratio,
+    mutation_odds: &'a Ratio,
     tree_nodes_count: usize,
     masteries_nodes_count: usize,
     max_nodes_count: usize,
     targets_count: usize,
-    rng: &'a mut ThreadRng,
+    dna_context: Arc<DnaContext>,
+    initial_population: Vec<Dna>,
     objectives: Vec<Box<dyn Objective<Dna>>>,
     constraints: Vec<Box<dyn Constraint<Dna>>>,
 }
@@ -193,20 +605,24 @@ impl<'a> Meta<'a, Dna> for Params<'a> {
     }
 
     fn crossover_odds(&self) -> &'a Ratio {
-        &Ratio(1, 1)
+        self.crossover_odds
     }
 
     fn mutation_odds(&self) -> &'a Ratio {
-        &Ratio(1, 1)
+        self.mutation_odds
     }
 
     fn random_solution(&mut self) -> Dna {
-        Dna::new(
-            DnaData::new(self.tree_nodes_count,
-                         self.masteries_nodes_count,
-                         self.targets_count,
-                         self.max_nodes_count)
-        )
+        match self.initial_population.pop() {
+            Some(dna) => dna,
+            None => Dna::new_with_context(
+                DnaData::new(self.tree_nodes_count,
+                             self.masteries_nodes_count,
+                             self.targets_count,
+                             self.max_nodes_count),
+                self.dna_context.clone()
+            )
+        }
     }
 
     fn objectives(&self) -> &Vec<Box<dyn Objective<Dna>>> {
@@ -233,24 +649,156 @@ impl UserData for LuaGeneticSolver {
         });
 
         methods.add_method("GetBestDna", |_lua_context, this, ()| {
+            let process_status = this.process_status.read().unwrap();
+
+            let best_dna = process_status.best_dna
+                .as_ref()
+                .ok_or_else(|| LuaError::RuntimeError(String::from("GetBestDna: no dna has been evaluated yet")))?;
+
             Ok(
                 LuaDna {
-                    reference: Rc::new(
-                        Dna {
-                            reference: this.process_status.read().unwrap().best_dna.as_ref().unwrap().reference.clone()
-                        }
-                    )
+                    reference: Rc::new(best_dna.clone())
                 }
             )
         });
 
+        // Seed of current or last solve, passing it back into StartSolve repeats the run
+        methods.add_method("GetSeed", |_lua_context, this, ()| {
+            Ok(this.solve_parameters.as_ref().map(|solve_parameters| solve_parameters.seed))
+        });
+
+        // Name of the stop condition which ended the run, nil while solve is running
+        methods.add_method("GetStopReason", |_lua_context, this, ()| {
+            Ok(this.process_status.read().unwrap().stop_reason.map(|stop_reason| stop_reason.name()))
+        });
+
+        methods.add_method("GetEvaluationsCount", |_lua_context, this, ()| {
+            Ok(this.process_status.read().unwrap().evaluations_count)
+        });
+
+        // Mean count of allocated nodes which differ between two dnas of the last generation
+        methods.add_method("GetPopulationDiversity", |_lua_context, this, ()| {
+            Ok(this.process_status.read().unwrap().population_diversity)
+        });
+
+        methods.add_method("GetReplacedDuplicatesCount", |_lua_context, this, ()| {
+            Ok(this.process_status.read().unwrap().replaced_duplicates_count)
+        });
+
+        // Text of the solver failure, nil when solver has not failed
+        methods.add_method("GetSolverError", |_lua_context, this, ()| {
+            Ok(this.process_status.read().unwrap().solver_error.clone())
+        });
+
+        // Text of the last worker failure, nil when workers had no errors
+        methods.add_method("GetLastWorkerError", |_lua_context, this, ()| {
+            Ok(this.workers_status.last_error.lock().unwrap().clone())
+        });
+
+        methods.add_method("GetWorkerErrorsCount", |_lua_context, this, ()| {
+            Ok(this.workers_status.errors_count.load(Ordering::SeqCst))
+        });
+
+        methods.add_method("GetAliveWorkersCount", |_lua_context, this, ()| {
+            Ok(this.workers_status.alive_workers_count.load(Ordering::SeqCst))
+        });
+
+        methods.add_method("GetCacheStatistics", |lua_context, this, ()| {
+            let cache_statistics = this.session.read().unwrap().fitness_cache.get_statistics();
+
+            let lookups_count = cache_statistics.hits_count + cache_statistics.misses_count;
+
+            let statistics_table = lua_context.create_table()?;
+
+            statistics_table.set("hits", cache_statistics.hits_count)?;
+            statistics_table.set("misses", cache_statistics.misses_count)?;
+            statistics_table.set("entries", cache_statistics.entries_count)?;
+            statistics_table.set("hitRate",
+                                 if lookups_count == 0 { 0.0 } else { cache_statistics.hits_count as f64 / lookups_count as f64 })?;
+
+            if let Some(stat_cache) = &this.session.read().unwrap().stat_cache
+            {
+                statistics_table.set("statHits", stat_cache.get_hits_count())?;
+                statistics_table.set("statEntries", stat_cache.get_entries_count())?;
+            }
+
+            Ok(statistics_table)
+        });
+
+        // Stat cache is saved when solve ends, this saves it while solve is running
+        methods.add_method("SaveStatCache", |_lua_context, this, ()| {
+            match &this.session.read().unwrap().stat_cache {
+                None => Err(LuaError::RuntimeError(String::from("SaveStatCache: stat cache is not enabled"))),
+                Some(stat_cache) => stat_cache.save().map_err(LuaError::RuntimeError)
+            }
+        });
+
+        methods.add_method("GetParetoFront", |lua_context, this, ()| {
+            let pareto_front_table = lua_context.create_table()?;
+
+            for (index, dna) in this.process_status.read().unwrap().pareto_front.iter().enumerate()
+            {
+                let entry_table = lua_context.create_table()?;
+
+                entry_table.set("dna", LuaDna {
+                    reference: Rc::new(dna.clone())
+                })?;
+                entry_table.set("fitnessScore", dna.fitness_score)?;
+                entry_table.set("fitnessScoreTargets", dna.fitness_score_targets.clone())?;
+
+                pareto_front_table.set(index + 1, entry_table)?;
+            }
+
+            Ok(pareto_front_table)
+        });
+
+        // Auto targets of current or last solve, target index is the position of target scores of dna
+        methods.add_method("GetActiveAutoTargets", |lua_context, this, ()| {
+            let auto_targets_table = lua_context.create_table()?;
+
+            let session = this.session.read().unwrap();
+
+            let auto_targets = session.targets
+                .iter()
+                .enumerate()
+                .filter_map(|(target_index, target)| target.get_auto_target_name().map(|name| (target_index, name, target)));
+
+            for (number, (target_index, name, target)) in auto_targets.enumerate()
+            {
+                let auto_target_table = lua_context.create_table()?;
+
+                auto_target_table.set("name", name)?;
+                auto_target_table.set("targetIndex", target_index + 1)?;
+                auto_target_table.set("weight", target.get_weight())?;
+                auto_target_table.set("constraint", target.is_constraint())?;
+
+                auto_targets_table.set(number + 1, auto_target_table)?;
+            }
+
+            Ok(auto_targets_table)
+        });
+
+        // Terms of fitness score of dna for every target, with targets and aggregation of current or last solve
+        methods.add_method("GetFitnessContributions", |_lua_context, this, lua_dna: LuaDna| {
+            let session = this.session.read().unwrap();
+
+            if lua_dna.reference.fitness_score_targets.len() != session.targets.len()
+            {
+                return Err(LuaError::RuntimeError(String::from("GetFitnessContributions: dna does not match targets of solve")));
+            }
+
+            let fitness_function_calculator = FitnessFunctionCalculator::new(session.targets.clone(), session.fitness_aggregation);
+
+            Ok(fitness_function_calculator.get_target_contributions(&lua_dna.reference.fitness_score_targets))
+        });
+
         methods.add_method_mut("StopSolve", |_lua_context, this, (): ()| {
 
             let process_status = this.process_status.read().unwrap();
 
             if process_status.is_progress == false
             {
-                panic!("Solve is not in progress");
+                return Err(LuaError::RuntimeError(String::from("StopSolve: solve is not in progress")));
             }
 
             this.is_received_stop_request.store(true, Ordering::SeqCst);
@@ -261,9 +809,19 @@ impl UserData for LuaGeneticSolver {
         methods.add_method_mut("CreateWorkers", |_lua_context, this, workers_count: Option<usize>| {
             if this.workers_was_created
             {
-                panic!("Workers already created")
+                return Err(LuaError::RuntimeError(String::from("CreateWorkers: workers are already created")));
+            }
+
+            if workers_count == Some(0)
+            {
+                return Err(LuaError::RuntimeError(String::from("CreateWorkers: workers count should be greater than 0")));
             }
 
+            let working_dir = env::current_dir()
+                .ok()
+                .and_then(|current_dir| current_dir.to_str().map(|current_dir| String::from(current_dir) + "/"))
+                .ok_or_else(|| LuaError::RuntimeError(String::from("CreateWorkers: cannot resolve current working directory")))?;
+
             let workers_count =
                 match workers_count {
                     None => {
@@ -289,14 +847,11 @@ impl UserData for LuaGeneticSolver {
                 let writer_dna_result_queue_channel = this.writer_dna_result_queue_channel.clone();
                 let workers_data = this.session.clone();
 
-                let working_dir = String::from(env::current_dir().unwrap().to_str().unwrap()) + "/";
-
-                thread::spawn(move || {
-                    worker_main(reader_dna_queue_channel,
-                                writer_dna_result_queue_channel,
-                                workers_data,
-                                &working_dir);
-                });
+                spawn_worker(reader_dna_queue_channel,
+                             writer_dna_result_queue_channel,
+                             workers_data,
+                             this.workers_status.clone(),
+                             working_dir.clone());
             }
 
             this.workers_was_created = true;
@@ -305,108 +860,519 @@ impl UserData for LuaGeneticSolver {
         });
 
         methods.add_method_mut("WaitSolve", |_lua_context, this, (): ()| {
-            this.main_thread.take().expect("Solve process is not started").join().unwrap();
+            let main_thread = this.main_thread
+                .take()
+                .ok_or_else(|| LuaError::RuntimeError(String::from("WaitSolve: solve process is not started")))?;
 
-            Ok(())
+            main_thread
+                .join()
+                .map_err(|_| LuaError::RuntimeError(String::from("WaitSolve: genetic solve thread has panicked")))
         });
 
-        methods.add_method_mut("StartSolve", |_lua_context, this, (
-            stop_generations_eps,
-            population_max_generation_size,
-            tree_nodes_count,
-            masteries_nodes_count,
-            target_normal_nodes_count,
-            target_ascendancy_nodes_count,
-            targets_table,
-            maximizes_table
-        ): (usize, usize, usize, usize, usize, usize, LuaTable, LuaTable)| {
-
-            if population_max_generation_size % 2 != 0
+        methods.add_method_mut("StartSolve", |lua_context, this, options_table: LuaTable| {
+            let options = parse_solve_options(&options_table)?;
+
+            let targets_table = options.targets_table.map_or_else(|| lua_context.create_table(), Ok)?;
+            let maximizes_table = options.maximizes_table.map_or_else(|| lua_context.create_table(), Ok)?;
+
+            let mut targets: Vec<Box<dyn Target>> = Vec::new();
+
+            let user_targets = create_targets_from_tables(targets_table, maximizes_table).map_err(|err| options_error(err.to_string()))?;
+
+            for user_target in user_targets
             {
-                panic!("population_max_generation_size should be 2");
+                targets.push(Box::new(user_target));
             }
 
-            let targets_count =
+            for auto_target_settings in options.auto_targets.iter().filter(|settings| settings.is_enabled)
+            {
+                targets.push(create_auto_target(auto_target_settings).map_err(options_error)?);
+            }
+
+            let build_tree = options.build_table.as_ref().map(read_build_tree).transpose()?;
+
+            let mut dna_encoder = options.build_table.as_ref().map(create_dna_encoder).transpose()?;
+
+            // Encoder resets nodes of build while it reads the tree, allocated tree of user should be restored
+            if let (Some(build_table), Some(build_tree)) = (&options.build_table, &build_tree)
+            {
+                if read_build_tree(build_table)? != *build_tree
                 {
-                    let mut process_status = this.process_status.write().unwrap();
+                    return Err(LuaError::RuntimeError(String::from("StartSolve: allocated tree of build was not restored after DnaEncoder creation")));
+                }
+            }
 
-                    if process_status.is_progress
-                    {
-                        panic!("Genetic solve already in progress");
+            let build_tree = build_tree.filter(|_| options.is_warm_start);
+
+            let (tree_nodes_count, masteries_nodes_count) =
+                match &dna_encoder {
+                    Some(dna_encoder) => {
+                        let tree_nodes_count = dna_encoder.get_tree_nodes_count();
+                        let masteries_nodes_count = dna_encoder.get_masteries_count();
+
+                        if options.tree_nodes_count.unwrap_or(tree_nodes_count) != tree_nodes_count
+                            || options.masteries_count.unwrap_or(masteries_nodes_count) != masteries_nodes_count
+                        {
+                            return Err(options_error(String::from("'treeNodesCount' and 'masteriesCount' do not match build tree")));
+                        }
+
+                        (tree_nodes_count, masteries_nodes_count)
+                    },
+                    None => match (options.tree_nodes_count, options.masteries_count) {
+                        (Some(tree_nodes_count), Some(masteries_nodes_count)) => (tree_nodes_count, masteries_nodes_count),
+                        _ => return Err(options_error(String::from("'treeNodesCount' and 'masteriesCount' are required when 'build' is not given")))
                     }
+                };
+
+            if options.max_mutate_cluster_size >= tree_nodes_count
+            {
+                return Err(options_error(format!("'mutationClusterSize' should be less than tree nodes count {}", tree_nodes_count)));
+            }
+
+            if let Some(node_id) = options.locked_node_ids.iter().find(|node_id| options.forbidden_node_ids.contains(node_id))
+            {
+                return Err(options_error(format!("node {} is both locked and forbidden", node_id)));
+            }
+
+            let build_tree_version = options.build_table.as_ref().map(read_tree_version).transpose()?.flatten();
+
+            let stat_cache_settings =
+                match (options.stat_cache_directory, options.stat_cache_build_key) {
+                    (Some(directory), Some(build_key)) => {
+                        let tree_version =
+                            match (options.stat_cache_tree_version.clone(), &options.build_table) {
+                                (Some(tree_version), _) => tree_version,
+                                (None, Some(_)) => {
+                                    build_tree_version
+                                        .clone()
+                                        .ok_or_else(|| options_error(String::from("build has no tree version, 'statCacheTreeVersion' should be given")))?
+                                },
+                                (None, None) => return Err(options_error(String::from("'statCacheDirectory' requires 'statCacheTreeVersion' when 'build' is not given")))
+                            };
+
+                        Some(StatCacheSettings {
+                            directory,
+                            build_key,
+                            tree_version,
+                            max_entries_count: options.stat_cache_max_entries
+                        })
+                    },
+                    _ => None
+                };
+
+            // Lua numbers are doubles, so generated seed is kept small enough to be reported back exactly
+            let seed = options.seed.unwrap_or_else(|| thread_rng().gen::<u32>() as u64);
+
+            let mut dna_context = DnaContext {
+                max_mutate_cluster_size: options.max_mutate_cluster_size,
+                mutation_mode: options.mutation_mode,
+                crossover_mode: options.crossover_mode,
+                repair_policy: options.repair_policy,
+                constraint_target_indexes: get_constraint_target_indexes(&targets),
+                rng: Mutex::new(StdRng::seed_from_u64(seed)),
+                ..DnaContext::default()
+            };
+
+            if let Some(dna_encoder) = &dna_encoder
+            {
+                dna_context.locked_node_indexes = dna_encoder.get_lockable_node_indexes(&options.locked_node_ids).map_err(options_error)?;
+                dna_context.forbidden_node_indexes = dna_encoder.get_lockable_node_indexes(&options.forbidden_node_ids).map_err(options_error)?;
+                dna_context.tree_graph = Some(Arc::new(dna_encoder.get_tree_graph()));
+            }
+
+            let dna_context = Arc::new(dna_context);
+
+            let max_nodes_count = options.target_normal_nodes_count + options.target_ascendancy_nodes_count;
+
+            let seed_dna_data =
+                match (&dna_encoder, &build_tree) {
+                    (Some(dna_encoder), Some(build_tree)) => Some(dna_encoder.convert_build_to_dna(build_tree, targets.len(), max_nodes_count)),
+                    _ => None
+                };
+
+            let empty_dna_data = DnaData::new(tree_nodes_count, masteries_nodes_count, targets.len(), max_nodes_count);
 
-                    process_status.is_progress = true;
+            // Otherwise locked nodes over budget would be silently missing in every build
+            if let Some(dna_encoder) = &mut dna_encoder
+            {
+                if !dna_context.locked_node_indexes.is_empty()
+                {
+                    let unallocated_locked_node_ids = dna_encoder.get_unallocated_locked_node_ids(&Dna::new_with_context(empty_dna_data.clone(), dna_context.clone()),
+                                                                                                 options.target_normal_nodes_count,
+                                                                                                 options.target_ascendancy_nodes_count)?;
+
+                    if !unallocated_locked_node_ids.is_empty()
+                    {
+                        return Err(options_error(format!("locked nodes {:?} with their paths do not fit into nodes budget", unallocated_locked_node_ids)));
+                    }
+                }
+            }
 
-                    process_status.best_dna = None;
-                    process_status.best_dna_number = 0;
+            // Islands get their populations one after another, each one of the mix
+            let initial_population =
+                if options.islands.is_empty()
+                {
+                    create_initial_population(&options.initial_population_mix,
+                                              options.population_size,
+                                              seed_dna_data,
+                                              &empty_dna_data,
+                                              &dna_context)
+                }
+                else
+                {
+                    options.islands
+                        .iter()
+                        .flat_map(|island| create_initial_population(&options.initial_population_mix,
+                                                                     island.population_size,
+                                                                     seed_dna_data.clone(),
+                                                                     &empty_dna_data,
+                                                                     &dna_context))
+                        .collect()
+                };
 
-                    let mut session_parameters = this.session.write().unwrap();
+            this.start_solve(
+                SolveParameters {
+                    stop_generations_eps: options.stop_generations_eps,
+                    population_max_generation_size: options.population_size,
+                    tree_nodes_count,
+                    masteries_nodes_count,
+                    tree_version: build_tree_version.or(options.stat_cache_tree_version),
+                    target_normal_nodes_count: options.target_normal_nodes_count,
+                    target_ascendancy_nodes_count: options.target_ascendancy_nodes_count,
+                    mutation_odds: options.mutation_odds,
+                    crossover_odds: options.crossover_odds,
+                    seed,
+                    stop_criteria: options.stop_criteria,
+                    stat_cache_settings,
+                    optimizer_type: options.optimizer_type,
+                    elites_count: options.elites_count,
+                    tournament_size: options.tournament_size,
+                    is_local_search_after_solve: options.is_local_search_after_solve,
+                    local_search_interval: options.local_search_interval,
+                    local_search_max_steps: options.local_search_max_steps,
+                    is_eliminate_duplicates: options.is_eliminate_duplicates,
+                    islands: options.islands,
+                    migration_interval: options.migration_interval,
+                    migration_rate: options.migration_rate,
+                    fitness_aggregation: options.fitness_aggregation
+                },
+                dna_context,
+                targets,
+                initial_population,
+                0
+            )
+        });
 
-                    session_parameters.target_normal_nodes_count = target_normal_nodes_count;
-                    session_parameters.target_ascendancy_nodes_count = target_ascendancy_nodes_count;
-                    session_parameters.number += 1;
+        methods.add_method_mut("StartSolveFromCheckpoint", |_lua_context, this, (path, build_table): (String, Option<LuaTable>)| {
+            let checkpoint = Checkpoint::load(&path).map_err(LuaError::RuntimeError)?;
 
-                    let user_targets = create_targets_from_tables(targets_table, maximizes_table);
+            // Workers decode dnas with their own build, checkpoint of another tree would fail on every dna
+            if let Some(build_table) = &build_table
+            {
+                let dna_encoder = create_dna_encoder(build_table)?;
 
-                    session_parameters.targets.clear();
+                if dna_encoder.get_tree_nodes_count() != checkpoint.solve_parameters.tree_nodes_count
+                    || dna_encoder.get_masteries_count() != checkpoint.solve_parameters.masteries_nodes_count
+                {
+                    return Err(LuaError::RuntimeError(String::from("StartSolveFromCheckpoint: checkpoint does not match tree of build")));
+                }
 
-                    for user_target in user_targets
+                if let (Some(checkpoint_tree_version), Some(tree_version)) = (&checkpoint.solve_parameters.tree_version, read_tree_version(build_table)?)
+                {
+                    if *checkpoint_tree_version != tree_version
                     {
-                        session_parameters.targets.push(Box::new(user_target));
+                        return Err(LuaError::RuntimeError(format!("StartSolveFromCheckpoint: checkpoint is made for tree version {}, build has {}", checkpoint_tree_version, tree_version)));
                     }
+                }
+            }
 
-                    session_parameters.targets.push(Box::new(AutoTargetManaCost{}));
-                    session_parameters.targets.push(Box::new(AutoTargetManaRegen{}));
-                    session_parameters.targets.push(Box::new(AutoTargetFromStatToStat{
-                        target_stat_name: String::from("ReqStr"),
-                        current_stat_name: String::from("Str"),
-                    }));
-                    session_parameters.targets.push(Box::new(AutoTargetFromStatToStat{
-                        target_stat_name: String::from("ReqInt"),
-                        current_stat_name: String::from("Int"),
-                    }));
-                    session_parameters.targets.push(Box::new(AutoTargetFromStatToStat{
-                        target_stat_name: String::from("ReqDex"),
-                        current_stat_name: String::from("Dex"),
-                    }));
+            let population = checkpoint.population
+                .into_iter()
+                .map(|dna_data| Dna::new_with_context(dna_data, checkpoint.dna_context.clone()))
+                .collect();
 
-                    this.is_received_stop_request.store(false, Ordering::SeqCst);
+            this.start_solve(checkpoint.solve_parameters,
+                             checkpoint.dna_context,
+                             checkpoint.targets,
+                             population,
+                             checkpoint.generation_number)
+        });
 
-                    this.current_generation_number.store(0, Ordering::SeqCst);
+        methods.add_method("SaveCheckpoint", |_lua_context, this, path: String| {
+            let checkpoint =
+                {
+                    let solve_parameters = this.solve_parameters
+                        .clone()
+                        .ok_or_else(|| LuaError::RuntimeError(String::from("Solve was not started")))?;
+
+                    let process_status = this.process_status.read().unwrap();
 
-                    session_parameters.targets.len()
+                    if process_status.population.is_empty() || process_status.island_populations.iter().any(|population| population.is_empty())
+                    {
+                        return Err(LuaError::RuntimeError(String::from("Population is not evaluated yet")));
+                    }
+
+                    Checkpoint {
+                        generation_number: this.current_generation_number.load(Ordering::SeqCst) as usize,
+                        solve_parameters,
+                        dna_context: process_status.population[0].context.clone(),
+                        targets: this.session.read().unwrap().targets.clone(),
+                        population: process_status.population
+                            .iter()
+                            .map(|dna| dna.reference.as_ref().clone())
+                            .collect()
+                    }
                 };
 
-            // Drain all current messages from previous iterations
-            while this.reader_dna_queue_channel.try_recv().is_ok() {}
-            while this.reader_dna_result_queue_channel.try_recv().is_ok() {}
-
-            let writer_dna_queue_channel = this.writer_dna_queue_channel.clone();
-            let reader_dna_result_queue_channel = this.reader_dna_result_queue_channel.clone();
-            let process_status = this.process_status.clone();
-            let is_received_stop_request = this.is_received_stop_request.clone();
-            let current_generation_number = this.current_generation_number.clone();
-            let thread = thread::spawn(move || {
-                genetic_solve(writer_dna_queue_channel,
+            checkpoint.save(&path).map_err(LuaError::RuntimeError)
+        });
+    }
+}
+
+impl LuaGeneticSolver
+{
+    fn start_solve(&mut self,
+                   solve_parameters: SolveParameters,
+                   dna_context: Arc<DnaContext>,
+                   targets: Vec<Box<dyn Target>>,
+                   initial_population: Vec<Dna>,
+                   start_generation_number: usize) -> LuaResult<()>
+    {
+        if !self.workers_was_created
+        {
+            return Err(LuaError::RuntimeError(String::from("Workers are not created, CreateWorkers should be called before solve")));
+        }
+
+        if self.workers_status.alive_workers_count.load(Ordering::SeqCst) == 0
+        {
+            let last_error = self.workers_status.last_error.lock().unwrap().clone().unwrap_or_default();
+
+            return Err(LuaError::RuntimeError(format!("All workers have failed: {}", last_error)));
+        }
+
+        let required_target_indexes: Vec<usize> = targets
+            .iter()
+            .enumerate()
+            .filter(|(_, target)| target.is_user_requirement())
+            .map(|(target_index, _)| target_index)
+            .collect();
+
+        let stat_cache =
+            match &solve_parameters.stat_cache_settings {
+                None => None,
+                Some(stat_cache_settings) => Some(Arc::new(StatCache::load(stat_cache_settings).map_err(LuaError::RuntimeError)?))
+            };
+
+        let targets_count =
+            {
+                let mut process_status = self.process_status.write().unwrap();
+
+                if process_status.is_progress
+                {
+                    return Err(LuaError::RuntimeError(String::from("Genetic solve is already in progress")));
+                }
+
+                process_status.is_progress = true;
+
+                process_status.best_dna = None;
+                process_status.best_dna_number = 0;
+                process_status.pareto_front.clear();
+                process_status.population.clear();
+                process_status.island_populations = vec![vec![]; solve_parameters.islands.len()];
+                process_status.stop_reason = None;
+                process_status.evaluations_count = 0;
+                process_status.population_diversity = 0.0;
+                process_status.replaced_duplicates_count = 0;
+                process_status.solver_error = None;
+
+                let mut session_parameters = self.session.write().unwrap();
+
+                session_parameters.target_normal_nodes_count = solve_parameters.target_normal_nodes_count;
+                session_parameters.target_ascendancy_nodes_count = solve_parameters.target_ascendancy_nodes_count;
+                session_parameters.number += 1;
+
+                session_parameters.targets = targets;
+                session_parameters.fitness_aggregation = solve_parameters.fitness_aggregation;
+                // Scores depend on targets, so cache lives for one session
+                session_parameters.fitness_cache = Arc::new(DnaCacheFitness::default());
+                session_parameters.stat_cache = stat_cache.clone();
+
+                self.is_received_stop_request.store(false, Ordering::SeqCst);
+
+                self.current_generation_number.store(start_generation_number as u64, Ordering::SeqCst);
+
+                session_parameters.targets.len()
+            };
+
+        self.solve_parameters = Some(solve_parameters.clone());
+
+        // Drain all current messages from previous iterations
+        while self.reader_dna_queue_channel.try_recv().is_ok() {}
+        while self.reader_dna_result_queue_channel.try_recv().is_ok() {}
+
+        let solve_runtime = SolveRuntime {
+            writer_dna_queue_channel: self.writer_dna_queue_channel.clone(),
+            process_status: self.process_status.clone(),
+            is_received_stop_request: self.is_received_stop_request.clone(),
+            workers_status: self.workers_status.clone(),
+            current_generation_number: self.current_generation_number.clone(),
+            start_generation_number,
+            targets_count,
+            required_target_indexes,
+            start_time: Instant::now(),
+            running_populations_count: Arc::new(AtomicUsize::new(solve_parameters.islands.len().max(1)))
+        };
+        let reader_dna_result_queue_channel = self.reader_dna_result_queue_channel.clone();
+        let process_status = self.process_status.clone();
+        let is_received_stop_request = self.is_received_stop_request.clone();
+        let thread = thread::spawn(move || {
+            let solve_result = panic::catch_unwind(AssertUnwindSafe(|| {
+                genetic_solve(solve_runtime,
                               reader_dna_result_queue_channel,
-                              process_status,
-                              is_received_stop_request,
-                              current_generation_number,
-                              stop_generations_eps,
-                              population_max_generation_size,
-                              tree_nodes_count,
-                              masteries_nodes_count,
-                              targets_count,
-                              target_normal_nodes_count + target_ascendancy_nodes_count)
-            });
+                              solve_parameters,
+                              dna_context,
+                              initial_population)
+            }));
 
-            this.main_thread = Some(thread);
+            // Otherwise IsProgress would stay true and next StartSolve would be refused
+            if let Err(panic_payload) = solve_result
+            {
+                report_solver_panic(&process_status, panic_payload.as_ref());
 
-            Ok(())
+                process_status.write().unwrap().is_progress = false;
+                is_received_stop_request.store(false, Ordering::SeqCst);
+            }
+
+            // Errors are not lost, SaveStatCache reports them on the next try
+            if let Some(stat_cache) = stat_cache
+            {
+                let _ = stat_cache.save();
+            }
         });
+
+        self.main_thread = Some(thread);
+
+        Ok(())
     }
 }
 
+fn create_runtime_processor(solve_runtime: &SolveRuntime,
+                            solve_parameters: &SolveParameters,
+                            reader_dna_result_queue_channel: Receiver<Box<DnaCommand>>,
+                            result_channel: Option<Sender<Box<DnaCommand>>>,
+                            island: Option<Island>) -> SolutionsRuntimeDnaProcessor
+{
+    let (writer_pending_result_channel, reader_pending_result_channel) = unbounded();
+
+    SolutionsRuntimeDnaProcessor {
+        writer_dna_queue_channel: solve_runtime.writer_dna_queue_channel.clone(),
+        reader_dna_result_queue_channel,
+        result_channel,
+        process_status: solve_runtime.process_status.clone(),
+        current_generation_number: solve_runtime.current_generation_number.clone(),
+        start_generation_number: solve_runtime.start_generation_number,
+        is_received_stop_request: solve_runtime.is_received_stop_request.clone(),
+        workers_status: solve_runtime.workers_status.clone(),
+        objectives: create_objectives(solve_runtime.targets_count),
+        stop_criteria: solve_parameters.stop_criteria.clone(),
+        required_target_indexes: solve_runtime.required_target_indexes.clone(),
+        start_time: solve_runtime.start_time,
+        generation_number: solve_runtime.start_generation_number,
+        island,
+        local_search_interval: solve_parameters.local_search_interval,
+        local_search_max_steps: solve_parameters.local_search_max_steps,
+        is_eliminate_duplicates: solve_parameters.is_eliminate_duplicates,
+        writer_pending_result_channel,
+        reader_pending_result_channel,
+        pending_dnas: HashMap::new(),
+        next_pending_dna_index: 0
+    }
+}
+
+fn run_population(solve_runtime: &SolveRuntime,
+                  solve_parameters: &SolveParameters,
+                  population_parameters: &PopulationParameters,
+                  dna_context: Arc<DnaContext>,
+                  initial_population: Vec<Dna>,
+                  optimizer_seed: u64,
+                  mut runtime_processor: SolutionsRuntimeDnaProcessor)
+{
+    let crossover_odds = odds_to_ratio(population_parameters.crossover_odds);
+    let mutation_odds = odds_to_ratio(population_parameters.mutation_odds);
+
+    let meta = Params {
+        population_max_generation_size: population_parameters.population_size,
+        crossover_odds: &crossover_odds,
+        mutation_odds: &mutation_odds,
+        tree_nodes_count: solve_parameters.tree_nodes_count,
+        masteries_nodes_count: solve_parameters.masteries_nodes_count,
+        max_nodes_count: solve_parameters.target_normal_nodes_count + solve_parameters.target_ascendancy_nodes_count,
+        targets_count: solve_runtime.targets_count,
+        dna_context,
+        initial_population,
+        objectives: match population_parameters.optimizer_type {
+            OptimizerType::Nsga2 => create_objectives(solve_runtime.targets_count),
+            // Processor still reports pareto front of all objectives
+            OptimizerType::Elitist | OptimizerType::SteadyState => vec![Box::new(FitnessScoreObjective{})]
+        },
+        constraints: vec![Box::new(TargetsConstraint{})]
+    };
+
+    let mut solve_evaluator: Box<dyn Evaluator> = Box::new(SolveEvaluator::new(solve_parameters.stop_generations_eps,
+                                                                               solve_runtime.process_status.clone(),
+                                                                               solve_runtime.running_populations_count.clone()));
+
+    let mut optimizer: Box<dyn Optimizer<Dna>> =
+        match population_parameters.optimizer_type {
+            OptimizerType::Nsga2 => Box::new(Nsga2Optimizer::new(meta, optimizer_seed)),
+            OptimizerType::Elitist => Box::new(ElitistOptimizer::new(meta,
+                                                                     optimizer_seed,
+                                                                     population_parameters.elites_count,
+                                                                     population_parameters.tournament_size)),
+            // Steady state sends candidates one by one, it does not fit into optimizer of sss_moo
+            OptimizerType::SteadyState => {
+                let mut optimizer = SteadyStateOptimizer::new(meta, optimizer_seed, population_parameters.tournament_size);
+
+                optimizer.optimize(&mut solve_evaluator, &mut runtime_processor);
+
+                return;
+            }
+        };
+
+    let mut runtime_processor: Box<dyn SolutionsRuntimeProcessor<Dna>> = Box::new(runtime_processor);
+
+    optimizer
+        .optimize(
+            &mut solve_evaluator,
+            &mut runtime_processor
+        );
+}
+
+// Local search is not stopped by stagnation, only by user, time, budget of evaluations and failed workers.
+// A step is not split, so the budget can be exceeded by the neighbours of one step
+fn create_local_search_interruption(is_received_stop_request: Arc<AtomicBool>,
+                                    process_status: Arc<RwLock<ProcessStatus>>,
+                                    start_time: Instant,
+                                    stop_criteria: &StopCriteria) -> impl Fn() -> bool
+{
+    let time_limit = stop_criteria.time_limit;
+    let max_evaluations = stop_criteria.max_evaluations;
+
+    move || {
+        let process_status = process_status.read().unwrap();
+
+        is_received_stop_request.load(Ordering::SeqCst)
+            || time_limit.is_some_and(|time_limit| start_time.elapsed() >= time_limit)
+            || max_evaluations.is_some_and(|max_evaluations| process_status.evaluations_count >= max_evaluations)
+            || process_status.stop_reason == Some(StopReason::WorkersFailed)
+    }
+}
+
+fn odds_to_ratio(odds: f64) -> Ratio
+{
+    Ratio((odds * ODDS_RATIO_DENOMINATOR as f64).round() as u32, ODDS_RATIO_DENOMINATOR)
+}
+
 pub fn create_genetic_solver(_: &Lua, (): ()) -> LuaResult<LuaGeneticSolver> {
     let (writer_dna_queue_channel, reader_dna_queue_channel) =
         unbounded();
@@ -423,70 +1389,178 @@ pub fn create_genetic_solver(_: &Lua, (): ()) -> LuaResult<LuaGeneticSolver> {
             number: 0,
             target_ascendancy_nodes_count: 0,
             target_normal_nodes_count: 0,
-            targets: vec![]
+            targets: vec![],
+            fitness_aggregation: FitnessAggregation::Product,
+            fitness_cache: Arc::new(DnaCacheFitness::default()),
+            stat_cache: None
         })),
         process_status: Arc::new(RwLock::new(ProcessStatus {
             best_dna: None,
             best_dna_number: 0,
+            pareto_front: vec![],
+            population: vec![],
+            island_populations: vec![],
+            stop_reason: None,
+            evaluations_count: 0,
+            population_diversity: 0.0,
+            replaced_duplicates_count: 0,
+            solver_error: None,
             is_progress: false
         })),
+        solve_parameters: None,
         main_thread: None,
         workers_was_created: false,
+        workers_status: Arc::new(WorkersStatus::default()),
         is_received_stop_request: Arc::new(AtomicBool::new(false)),
         current_generation_number: Arc::new(Default::default()),
     })
 }
 
-pub fn genetic_solve(writer_dna_queue_channel: Sender<Box<DnaCommand>>,
-                     reader_dna_result_queue_channel: Receiver<Box<DnaCommand>>,
-                     process_status: Arc<RwLock<ProcessStatus>>,
-                     is_received_stop_request: Arc<AtomicBool>,
-                     current_generation_number: Arc<AtomicU64>,
-                     stop_generations_eps: usize,
-                     population_max_generation_size: usize,
-                     tree_nodes_count: usize,
-                     masteries_nodes_count: usize,
-                     targets_count: usize,
-                     target_nodes_count: usize)
+// Shared by processors of all populations of solve
+#[derive(Clone)]
+struct SolveRuntime
 {
-    let mut objectives: Vec<Box<dyn Objective<Dna>>> = Vec::new();
+    writer_dna_queue_channel: Sender<Box<DnaCommand>>,
+    process_status: Arc<RwLock<ProcessStatus>>,
+    is_received_stop_request: Arc<AtomicBool>,
+    workers_status: Arc<WorkersStatus>,
+    current_generation_number: Arc<AtomicU64>,
+    start_generation_number: usize,
+    targets_count: usize,
+    required_target_indexes: Vec<usize>,
+    start_time: Instant,
+    running_populations_count: Arc<AtomicUsize>
+}
 
-    for target_index in 0..targets_count
+// Lock can be poisoned by the panic, status is still consistent enough to report the failure
+fn report_solver_panic(process_status: &RwLock<ProcessStatus>, panic_payload: &(dyn Any + Send))
+{
+    process_status.clear_poison();
+
+    let mut process_status = process_status.write().unwrap();
+
+    process_status.stop_reason = Some(StopReason::SolverFailed);
+    process_status.solver_error = Some(format!("Solver has panicked: {}", panic_message(panic_payload)));
+}
+
+fn genetic_solve(solve_runtime: SolveRuntime,
+                 reader_dna_result_queue_channel: Receiver<Box<DnaCommand>>,
+                 solve_parameters: SolveParameters,
+                 dna_context: Arc<DnaContext>,
+                 initial_population: Vec<Dna>)
+{
+    let process_status = &solve_runtime.process_status;
+    let is_received_stop_request = &solve_runtime.is_received_stop_request;
+
+    // Selection uses its own generator, dna operators use the one from dna context
+    let optimizer_seed = solve_parameters.seed.wrapping_add(1);
+
+    if solve_parameters.islands.is_empty()
     {
-        objectives.push(Box::new(TargetObjective {
-            target_index
-        }));
+        let runtime_processor = create_runtime_processor(&solve_runtime, &solve_parameters, reader_dna_result_queue_channel.clone(), None, None);
+
+        run_population(&solve_runtime,
+                       &solve_parameters,
+                       &solve_parameters.get_population_parameters(),
+                       dna_context,
+                       initial_population,
+                       optimizer_seed,
+                       runtime_processor);
+    }
+    else
+    {
+        let migration = Arc::new(Migration::new(solve_parameters.islands.len(),
+                                                solve_parameters.migration_interval,
+                                                solve_parameters.migration_rate));
+
+        let mut initial_population = initial_population.into_iter();
+
+        let island_threads: Vec<JoinHandle<()>> = solve_parameters.islands
+            .iter()
+            .enumerate()
+            .map(|(island_index, population_parameters)| {
+                // Island threads go at their own pace, shared generator would make seeded runs differ.
+                // Resumed run is reseeded by generation number like dna context of checkpoint
+                let island_dna_context = Arc::new(dna_context.with_seed(solve_parameters.seed
+                    .wrapping_add(solve_runtime.start_generation_number as u64)
+                    .wrapping_add(island_index as u64)));
+
+                let island_initial_population: Vec<Dna> = initial_population
+                    .by_ref()
+                    .take(population_parameters.population_size)
+                    .map(|mut dna| {
+                        dna.context = island_dna_context.clone();
+
+                        dna
+                    })
+                    .collect();
+
+                let solve_runtime = solve_runtime.clone();
+                let solve_parameters = solve_parameters.clone();
+                let population_parameters = population_parameters.clone();
+                let dna_context = island_dna_context;
+                let island = Island {
+                    index: island_index,
+                    migration: migration.clone()
+                };
+
+                thread::spawn(move || {
+                    // Each island reads results of its own dnas only
+                    let (writer_island_result_channel, reader_island_result_channel) = unbounded();
+
+                    let runtime_processor = create_runtime_processor(&solve_runtime,
+                                                                     &solve_parameters,
+                                                                     reader_island_result_channel,
+                                                                     Some(writer_island_result_channel),
+                                                                     Some(island));
+
+                    let island_result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        run_population(&solve_runtime,
+                                       &solve_parameters,
+                                       &population_parameters,
+                                       dna_context,
+                                       island_initial_population,
+                                       optimizer_seed.wrapping_add(island_index as u64),
+                                       runtime_processor)
+                    }));
+
+                    // Stop reason is set, so the rest of islands stop too
+                    if let Err(panic_payload) = island_result
+                    {
+                        report_solver_panic(&solve_runtime.process_status, panic_payload.as_ref());
+                    }
+                })
+            })
+            .collect();
+
+        for island_thread in island_threads
+        {
+            // Panics are caught inside of island thread
+            let _ = island_thread.join();
+        }
     }
 
-    objectives.push(Box::new(FitnessScoreObjective{}));
+    let best_dna = process_status.read().unwrap().best_dna.clone();
 
-    let meta = Params {
-        population_max_generation_size,
-        tree_nodes_count,
-        masteries_nodes_count,
-        max_nodes_count: target_nodes_count,
-        targets_count,
-        rng: &mut thread_rng(),
-        objectives,
-        constraints: vec![]
-    };
+    if let (true, Some(best_dna)) = (solve_parameters.is_local_search_after_solve, best_dna)
+    {
+        let mut runtime_processor = create_runtime_processor(&solve_runtime, &solve_parameters, reader_dna_result_queue_channel, None, None);
 
-    let mut default_evaluator: Box<(dyn Evaluator)> = Box::new(DefaultEvaluator::new(stop_generations_eps));
-    let mut runtime_processor: Box<(dyn SolutionsRuntimeProcessor<Dna>)> = Box::new(SolutionsRuntimeDnaProcessor {
-        writer_dna_queue_channel,
-        reader_dna_result_queue_channel,
-        process_status: process_status.clone(),
-        current_generation_number,
-        is_received_stop_request: is_received_stop_request.clone(),
-        best_solution_fitness: -1.0
-    });
+        let is_interrupted = create_local_search_interruption(is_received_stop_request.clone(),
+                                                              process_status.clone(),
+                                                              solve_runtime.start_time,
+                                                              &solve_parameters.stop_criteria);
 
-    let mut optimizer = NSGA2Optimizer::new(meta);
-    optimizer
-        .optimize(
-            &mut default_evaluator,
-            &mut runtime_processor
-        );
+        let polished_dna = polish_dna(&mut runtime_processor, &best_dna, solve_parameters.local_search_max_steps, &is_interrupted);
+
+        if is_better_dna(&polished_dna, &best_dna)
+        {
+            let mut process_status = process_status.write().unwrap();
+
+            process_status.best_dna = Some(polished_dna);
+            process_status.best_dna_number += 1;
+        }
+    }
 
     {
         process_status.write().unwrap().is_progress = false;
@@ -0,0 +1,158 @@
+use rand::prelude::SliceRandom;
+use crate::dna::Dna;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RepairPolicy
+{
+    // Trims random nodes
+    Random,
+    // Trims nodes furthest by links from class start, falls back to random without tree graph
+    Distance,
+    // Trims nodes which decoder could not allocate for the last evaluated parent first, then random ones
+    Unreachable
+}
+
+impl RepairPolicy
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            RepairPolicy::Random => "random",
+            RepairPolicy::Distance => "distance",
+            RepairPolicy::Unreachable => "unreachable"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RepairPolicy>
+    {
+        match name {
+            "random" => Some(RepairPolicy::Random),
+            "distance" => Some(RepairPolicy::Distance),
+            "unreachable" => Some(RepairPolicy::Unreachable),
+            _ => None
+        }
+    }
+}
+
+// Brings dna back into nodes budget after genetic operators, otherwise decoder drops excess nodes silently.
+// Locked nodes are never trimmed, but they take their place in nodes budget
+pub fn repair_dna(dna: &mut Dna)
+{
+    let context = dna.context.clone();
+
+    let max_count_unlocked_nodes = dna.max_count_nodes.saturating_sub(context.locked_node_indexes.len());
+
+    let mut selected_indexes: Vec<usize> = dna.body_nodes
+        .iter()
+        .enumerate()
+        .filter(|(node_index, nucl)| **nucl == 1 && !context.locked_node_indexes.contains(node_index))
+        .map(|(node_index, _)| node_index)
+        .collect();
+
+    if selected_indexes.len() <= max_count_unlocked_nodes
+    {
+        return;
+    }
+
+    selected_indexes.shuffle(&mut *context.rng.lock().unwrap());
+
+    // Nodes to keep go first, sort is stable so equal ones stay in random order
+    match (context.repair_policy, &context.tree_graph) {
+        (RepairPolicy::Distance, Some(tree_graph)) => {
+            let root_distances = tree_graph.get_root_distances();
+
+            selected_indexes.sort_by_key(|node_index| root_distances[*node_index]);
+        },
+        (RepairPolicy::Unreachable, _) => {
+            selected_indexes.sort_by_key(|node_index| dna.unreachable_node_indexes.contains(node_index));
+        },
+        _ => {}
+    }
+
+    for node_index in selected_indexes.drain(max_count_unlocked_nodes..)
+    {
+        dna.body_nodes[node_index] = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::dna::{DnaContext, DnaData};
+    use crate::tree_graph::TreeGraph;
+    use super::*;
+
+    // Chain of nodes from root 0, distance of node is its index
+    fn create_dna(repair_policy: RepairPolicy, max_count_nodes: usize) -> Dna
+    {
+        let nodes_count: usize = 8;
+
+        let context = DnaContext {
+            locked_node_indexes: vec![6],
+            repair_policy,
+            tree_graph: Some(Arc::new(TreeGraph {
+                linked_indexes: (0..nodes_count)
+                    .map(|node_index| [node_index.checked_sub(1), Some(node_index + 1).filter(|next_index| *next_index < nodes_count)]
+                        .into_iter()
+                        .flatten()
+                        .collect())
+                    .collect(),
+                root_indexes: vec![0],
+                mastery_effects_counts: vec![]
+            })),
+            ..DnaContext::default()
+        };
+
+        let mut dna = Dna::new_with_context(DnaData::new(nodes_count, 0, 0, max_count_nodes), Arc::new(context));
+
+        dna.body_nodes[1..8].fill(1);
+
+        dna
+    }
+
+    fn get_selected_indexes(dna: &Dna) -> Vec<usize>
+    {
+        (0..dna.body_nodes.len()).filter(|node_index| dna.body_nodes[*node_index] == 1).collect()
+    }
+
+    #[test]
+    fn dna_within_budget_is_not_changed() {
+        let mut dna = create_dna(RepairPolicy::Random, 7);
+
+        repair_dna(&mut dna);
+
+        assert_eq!(get_selected_indexes(&dna), vec![1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn random_repair_keeps_locked_nodes_in_budget() {
+        let mut dna = create_dna(RepairPolicy::Random, 3);
+
+        repair_dna(&mut dna);
+
+        let selected_indexes = get_selected_indexes(&dna);
+
+        assert_eq!(selected_indexes.len(), 3);
+        assert!(selected_indexes.contains(&6));
+    }
+
+    #[test]
+    fn distance_repair_trims_furthest_nodes() {
+        let mut dna = create_dna(RepairPolicy::Distance, 3);
+
+        repair_dna(&mut dna);
+
+        assert_eq!(get_selected_indexes(&dna), vec![1, 2, 6]);
+    }
+
+    #[test]
+    fn unreachable_repair_trims_unreachable_nodes_first() {
+        let mut dna = create_dna(RepairPolicy::Unreachable, 5);
+
+        dna.unreachable_node_indexes = vec![2, 4];
+
+        repair_dna(&mut dna);
+
+        assert_eq!(get_selected_indexes(&dna), vec![1, 3, 5, 6, 7]);
+    }
+}
@@ -0,0 +1,623 @@
+use std::time::Duration;
+use mlua::FromLua;
+use mlua::prelude::{LuaError, LuaResult, LuaTable, LuaValue};
+use crate::dna::{CrossoverMode, MutationMode};
+use crate::fitness_function_calculator::FitnessAggregation;
+use crate::auto_targets::{get_default_auto_target_settings, AutoTargetSettings, AUTO_TARGETS};
+use crate::initial_population::InitialPopulationMix;
+use crate::islands::PopulationParameters;
+use crate::repair::RepairPolicy;
+use crate::stop_criteria::StopCriteria;
+
+pub const DEFAULT_STOP_GENERATIONS_EPS: usize = 100;
+pub const DEFAULT_POPULATION_SIZE: usize = 100;
+pub const DEFAULT_ASCENDANCY_NODES_COUNT: usize = 8;
+pub const DEFAULT_MUTATION_ODDS: f64 = 1.0;
+pub const DEFAULT_CROSSOVER_ODDS: f64 = 1.0;
+pub const DEFAULT_MAX_MUTATE_CLUSTER_SIZE: usize = 4;
+pub const DEFAULT_ELITES_COUNT: usize = 2;
+pub const DEFAULT_TOURNAMENT_SIZE: usize = 2;
+pub const DEFAULT_LOCAL_SEARCH_MAX_STEPS: usize = 10;
+pub const DEFAULT_MIGRATION_INTERVAL: usize = 10;
+pub const DEFAULT_MIGRATION_RATE: usize = 2;
+pub const DEFAULT_STAT_CACHE_MAX_ENTRIES: usize = 100_000;
+pub const DEFAULT_ELIMINATE_DUPLICATES: bool = true;
+
+const KNOWN_OPTIONS: [&str; 41] = [
+    "stopGenerationsEps",
+    "populationSize",
+    "treeNodesCount",
+    "masteriesCount",
+    "normalNodesCount",
+    "ascendancyNodesCount",
+    "targets",
+    "maximizes",
+    "build",
+    "warmStart",
+    "lockedNodes",
+    "forbiddenNodes",
+    "mutationOdds",
+    "crossoverOdds",
+    "mutationClusterSize",
+    "seed",
+    "timeLimitSeconds",
+    "maxEvaluations",
+    "maxGenerations",
+    "stopWhenTargetsMet",
+    "statCacheDirectory",
+    "statCacheBuildKey",
+    "statCacheTreeVersion",
+    "statCacheMaxEntries",
+    "optimizer",
+    "elitesCount",
+    "tournamentSize",
+    "localSearch",
+    "localSearchInterval",
+    "localSearchMaxSteps",
+    "mutationMode",
+    "crossoverMode",
+    "repairPolicy",
+    "initialPopulationMix",
+    "eliminateDuplicates",
+    "islands",
+    "migrationInterval",
+    "migrationRate",
+    "fitnessAggregation",
+    "autoTargetConstraints",
+    "autoTargets"
+];
+
+// Fields of auto target settings in 'autoTargets'
+const KNOWN_AUTO_TARGET_OPTIONS: [&str; 3] = [
+    "enabled",
+    "weight",
+    "constraint"
+];
+
+// Options of population which island can change, others are common for all islands
+const KNOWN_ISLAND_OPTIONS: [&str; 6] = [
+    "optimizer",
+    "populationSize",
+    "mutationOdds",
+    "crossoverOdds",
+    "elitesCount",
+    "tournamentSize"
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OptimizerType
+{
+    // Pareto selection on every target and fitness score
+    Nsga2,
+    // Selection on fitness score only
+    Elitist,
+    // Selection on fitness score only, offspring is sent as soon as any worker is free
+    SteadyState
+}
+
+impl OptimizerType
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            OptimizerType::Nsga2 => "nsga2",
+            OptimizerType::Elitist => "elitist",
+            OptimizerType::SteadyState => "steadyState"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<OptimizerType>
+    {
+        match name {
+            "nsga2" => Some(OptimizerType::Nsga2),
+            "elitist" => Some(OptimizerType::Elitist),
+            "steadyState" => Some(OptimizerType::SteadyState),
+            _ => None
+        }
+    }
+}
+
+// Options of StartSolve. Tree sizes can be omitted when build is given
+pub struct SolveOptions<'lua>
+{
+    pub stop_generations_eps: usize,
+    pub population_size: usize,
+    pub tree_nodes_count: Option<usize>,
+    pub masteries_count: Option<usize>,
+    pub target_normal_nodes_count: usize,
+    pub target_ascendancy_nodes_count: usize,
+    pub targets_table: Option<LuaTable<'lua>>,
+    pub maximizes_table: Option<LuaTable<'lua>>,
+    pub build_table: Option<LuaTable<'lua>>,
+    pub is_warm_start: bool,
+    pub initial_population_mix: InitialPopulationMix,
+    pub locked_node_ids: Vec<i64>,
+    pub forbidden_node_ids: Vec<i64>,
+    pub mutation_odds: f64,
+    pub crossover_odds: f64,
+    pub max_mutate_cluster_size: usize,
+    pub mutation_mode: MutationMode,
+    pub crossover_mode: CrossoverMode,
+    pub repair_policy: RepairPolicy,
+    pub seed: Option<u64>,
+    pub stop_criteria: StopCriteria,
+    // Tree version of the cache file is taken from build when it is not given
+    pub stat_cache_directory: Option<String>,
+    pub stat_cache_build_key: Option<String>,
+    pub stat_cache_tree_version: Option<String>,
+    pub stat_cache_max_entries: usize,
+    pub optimizer_type: OptimizerType,
+    pub elites_count: usize,
+    pub tournament_size: usize,
+    pub is_local_search_after_solve: bool,
+    pub local_search_interval: Option<usize>,
+    pub local_search_max_steps: usize,
+    pub is_eliminate_duplicates: bool,
+    // Empty when solve runs one population
+    pub islands: Vec<PopulationParameters>,
+    pub migration_interval: usize,
+    pub migration_rate: usize,
+    pub fitness_aggregation: FitnessAggregation,
+    // Every auto target of registry, disabled ones are not added
+    pub auto_targets: Vec<AutoTargetSettings>
+}
+
+pub fn parse_solve_options<'lua>(options_table: &LuaTable<'lua>) -> LuaResult<SolveOptions<'lua>>
+{
+    for option_entry in options_table.clone().pairs::<LuaValue, LuaValue>()
+    {
+        let (option_key, _) = option_entry?;
+
+        match &option_key {
+            LuaValue::String(option_name) => {
+                let option_name = option_name.to_str()?;
+
+                if !KNOWN_OPTIONS.contains(&option_name)
+                {
+                    return Err(options_error(format!("unknown option '{}'", option_name)));
+                }
+            },
+            _ => return Err(options_error(format!("option names should be strings, got {}", option_key.type_name())))
+        }
+    }
+
+    let stop_generations_eps = get_option(options_table, "stopGenerationsEps")?.unwrap_or(DEFAULT_STOP_GENERATIONS_EPS);
+
+    if stop_generations_eps == 0
+    {
+        return Err(options_error(String::from("'stopGenerationsEps' should be greater than 0")));
+    }
+
+    let population_size = get_option(options_table, "populationSize")?.unwrap_or(DEFAULT_POPULATION_SIZE);
+
+    if population_size < 2 || population_size % 2 != 0
+    {
+        return Err(options_error(format!("'populationSize' should be an even number greater than 1, got {}", population_size)));
+    }
+
+    let target_normal_nodes_count = get_option(options_table, "normalNodesCount")?
+        .ok_or_else(|| options_error(String::from("'normalNodesCount' is required")))?;
+
+    let build_table: Option<LuaTable> = get_option(options_table, "build")?;
+
+    let tree_nodes_count = get_option(options_table, "treeNodesCount")?;
+    let masteries_count = get_option(options_table, "masteriesCount")?;
+
+    if build_table.is_none() && (tree_nodes_count.is_none() || masteries_count.is_none())
+    {
+        return Err(options_error(String::from("'treeNodesCount' and 'masteriesCount' are required when 'build' is not given")));
+    }
+
+    let is_warm_start = get_option(options_table, "warmStart")?.unwrap_or(false);
+
+    if is_warm_start && build_table.is_none()
+    {
+        return Err(options_error(String::from("'warmStart' requires 'build'")));
+    }
+
+    // Warm start seeds whole population, solves with build start from random subtrees
+    let initial_population_mix =
+        match get_option::<LuaTable>(options_table, "initialPopulationMix")? {
+            Some(mix_table) => parse_initial_population_mix(&mix_table)?,
+            None if is_warm_start => InitialPopulationMix { random: 0.0, seeded: 1.0, empty: 0.0 },
+            None if build_table.is_some() => InitialPopulationMix { random: 1.0, seeded: 0.0, empty: 0.0 },
+            None => InitialPopulationMix { random: 0.0, seeded: 0.0, empty: 1.0 }
+        };
+
+    if initial_population_mix.seeded > 0.0 && !is_warm_start
+    {
+        return Err(options_error(String::from("'initialPopulationMix' seeded share requires 'warmStart'")));
+    }
+
+    if initial_population_mix.random > 0.0 && build_table.is_none()
+    {
+        return Err(options_error(String::from("'initialPopulationMix' random share requires 'build'")));
+    }
+
+    let locked_node_ids: Vec<i64> = get_option(options_table, "lockedNodes")?.unwrap_or_default();
+    let forbidden_node_ids: Vec<i64> = get_option(options_table, "forbiddenNodes")?.unwrap_or_default();
+
+    if (!locked_node_ids.is_empty() || !forbidden_node_ids.is_empty()) && build_table.is_none()
+    {
+        return Err(options_error(String::from("'lockedNodes' and 'forbiddenNodes' require 'build'")));
+    }
+
+    let mutation_odds = get_odds_option(options_table, "mutationOdds", DEFAULT_MUTATION_ODDS)?;
+    let crossover_odds = get_odds_option(options_table, "crossoverOdds", DEFAULT_CROSSOVER_ODDS)?;
+
+    let max_mutate_cluster_size = get_option(options_table, "mutationClusterSize")?.unwrap_or(DEFAULT_MAX_MUTATE_CLUSTER_SIZE);
+
+    if max_mutate_cluster_size == 0
+    {
+        return Err(options_error(String::from("'mutationClusterSize' should be greater than 0")));
+    }
+
+    let mutation_mode =
+        match get_option::<String>(options_table, "mutationMode")? {
+            None => MutationMode::Cluster,
+            Some(mutation_mode_name) => MutationMode::from_name(&mutation_mode_name)
+                .ok_or_else(|| options_error(format!("unknown mutation mode '{}', expected 'cluster' or 'graph'", mutation_mode_name)))?
+        };
+
+    if mutation_mode == MutationMode::Graph && build_table.is_none()
+    {
+        return Err(options_error(String::from("'mutationMode' graph requires 'build'")));
+    }
+
+    // Region crossover needs tree graph of build, solves without build keep range crossover
+    let crossover_mode =
+        match get_option::<String>(options_table, "crossoverMode")? {
+            None => CrossoverMode::Region,
+            Some(crossover_mode_name) => CrossoverMode::from_name(&crossover_mode_name)
+                .ok_or_else(|| options_error(format!("unknown crossover mode '{}', expected 'range' or 'region'", crossover_mode_name)))?
+        };
+
+    let repair_policy =
+        match get_option::<String>(options_table, "repairPolicy")? {
+            None => RepairPolicy::Random,
+            Some(repair_policy_name) => RepairPolicy::from_name(&repair_policy_name)
+                .ok_or_else(|| options_error(format!("unknown repair policy '{}', expected 'random', 'distance' or 'unreachable'", repair_policy_name)))?
+        };
+
+    let fitness_aggregation =
+        match get_option::<String>(options_table, "fitnessAggregation")? {
+            None => FitnessAggregation::Product,
+            Some(fitness_aggregation_name) => FitnessAggregation::from_name(&fitness_aggregation_name)
+                .ok_or_else(|| options_error(format!("unknown fitness aggregation '{}', expected 'product', 'geometricMean', 'sum' or 'chebyshev'", fitness_aggregation_name)))?
+        };
+
+    if repair_policy == RepairPolicy::Distance && build_table.is_none()
+    {
+        return Err(options_error(String::from("'repairPolicy' distance requires 'build'")));
+    }
+
+    let time_limit_seconds: Option<f64> = get_option(options_table, "timeLimitSeconds")?;
+
+    if let Some(time_limit_seconds) = time_limit_seconds
+    {
+        if !time_limit_seconds.is_finite() || time_limit_seconds <= 0.0
+        {
+            return Err(options_error(format!("'timeLimitSeconds' should be greater than 0, got {}", time_limit_seconds)));
+        }
+    }
+
+    let max_evaluations: Option<usize> = get_option(options_table, "maxEvaluations")?;
+    let max_generations: Option<usize> = get_option(options_table, "maxGenerations")?;
+
+    if max_evaluations == Some(0) || max_generations == Some(0)
+    {
+        return Err(options_error(String::from("'maxEvaluations' and 'maxGenerations' should be greater than 0")));
+    }
+
+    let stop_criteria = StopCriteria {
+        time_limit: time_limit_seconds.map(Duration::from_secs_f64),
+        max_evaluations,
+        max_generations,
+        is_stop_when_targets_met: get_option(options_table, "stopWhenTargetsMet")?.unwrap_or(false)
+    };
+
+    let stat_cache_directory: Option<String> = get_option(options_table, "statCacheDirectory")?;
+    let stat_cache_build_key: Option<String> = get_option(options_table, "statCacheBuildKey")?;
+    let stat_cache_tree_version: Option<String> = get_option(options_table, "statCacheTreeVersion")?;
+
+    if stat_cache_directory.is_some()
+    {
+        if stat_cache_build_key.is_none()
+        {
+            return Err(options_error(String::from("'statCacheDirectory' requires 'statCacheBuildKey'")));
+        }
+
+        if stat_cache_tree_version.is_none() && build_table.is_none()
+        {
+            return Err(options_error(String::from("'statCacheDirectory' requires 'statCacheTreeVersion' when 'build' is not given")));
+        }
+    }
+
+    let stat_cache_max_entries = get_option(options_table, "statCacheMaxEntries")?.unwrap_or(DEFAULT_STAT_CACHE_MAX_ENTRIES);
+
+    if stat_cache_max_entries == 0
+    {
+        return Err(options_error(String::from("'statCacheMaxEntries' should be greater than 0")));
+    }
+
+    let optimizer_type =
+        match get_option::<String>(options_table, "optimizer")? {
+            None => OptimizerType::Nsga2,
+            Some(optimizer_name) => OptimizerType::from_name(&optimizer_name)
+                .ok_or_else(|| options_error(format!("unknown optimizer '{}', expected 'nsga2', 'elitist' or 'steadyState'", optimizer_name)))?
+        };
+
+    let elites_count = get_option(options_table, "elitesCount")?.unwrap_or(DEFAULT_ELITES_COUNT);
+
+    if elites_count >= population_size
+    {
+        return Err(options_error(format!("'elitesCount' should be less than population size {}", population_size)));
+    }
+
+    let tournament_size = get_option(options_table, "tournamentSize")?.unwrap_or(DEFAULT_TOURNAMENT_SIZE);
+
+    if tournament_size == 0
+    {
+        return Err(options_error(String::from("'tournamentSize' should be greater than 0")));
+    }
+
+    let is_local_search_after_solve = get_option(options_table, "localSearch")?.unwrap_or(false);
+    let local_search_interval: Option<usize> = get_option(options_table, "localSearchInterval")?;
+
+    if local_search_interval == Some(0)
+    {
+        return Err(options_error(String::from("'localSearchInterval' should be greater than 0")));
+    }
+
+    if (is_local_search_after_solve || local_search_interval.is_some()) && build_table.is_none()
+    {
+        return Err(options_error(String::from("'localSearch' and 'localSearchInterval' require 'build'")));
+    }
+
+    let population_parameters = PopulationParameters {
+        optimizer_type,
+        population_size,
+        mutation_odds,
+        crossover_odds,
+        elites_count,
+        tournament_size
+    };
+
+    let islands =
+        match get_option::<Vec<LuaTable>>(options_table, "islands")? {
+            None => vec![],
+            Some(island_tables) => {
+                if island_tables.len() < 2
+                {
+                    return Err(options_error(String::from("'islands' should have at least 2 islands")));
+                }
+
+                island_tables
+                    .iter()
+                    .enumerate()
+                    .map(|(island_index, island_table)| parse_island(island_table, island_index + 1, &population_parameters))
+                    .collect::<LuaResult<Vec<PopulationParameters>>>()?
+            }
+        };
+
+    let migration_interval = get_option(options_table, "migrationInterval")?.unwrap_or(DEFAULT_MIGRATION_INTERVAL);
+    let migration_rate = get_option(options_table, "migrationRate")?.unwrap_or(DEFAULT_MIGRATION_RATE);
+
+    if migration_interval == 0
+    {
+        return Err(options_error(String::from("'migrationInterval' should be greater than 0")));
+    }
+
+    if let Some(island) = islands.iter().find(|island| migration_rate >= island.population_size)
+    {
+        return Err(options_error(format!("'migrationRate' should be less than island population size {}", island.population_size)));
+    }
+
+    Ok(SolveOptions {
+        stop_generations_eps,
+        population_size,
+        tree_nodes_count,
+        masteries_count,
+        target_normal_nodes_count,
+        target_ascendancy_nodes_count: get_option(options_table, "ascendancyNodesCount")?.unwrap_or(DEFAULT_ASCENDANCY_NODES_COUNT),
+        targets_table: get_option(options_table, "targets")?,
+        maximizes_table: get_option(options_table, "maximizes")?,
+        build_table,
+        is_warm_start,
+        initial_population_mix,
+        locked_node_ids,
+        forbidden_node_ids,
+        mutation_odds,
+        crossover_odds,
+        max_mutate_cluster_size,
+        mutation_mode,
+        crossover_mode,
+        repair_policy,
+        seed: get_option(options_table, "seed")?,
+        stop_criteria,
+        stat_cache_directory,
+        stat_cache_build_key,
+        stat_cache_tree_version,
+        stat_cache_max_entries,
+        optimizer_type,
+        elites_count,
+        tournament_size,
+        is_local_search_after_solve,
+        local_search_interval,
+        local_search_max_steps: get_option(options_table, "localSearchMaxSteps")?.unwrap_or(DEFAULT_LOCAL_SEARCH_MAX_STEPS),
+        is_eliminate_duplicates: get_option(options_table, "eliminateDuplicates")?.unwrap_or(DEFAULT_ELIMINATE_DUPLICATES),
+        islands,
+        migration_interval,
+        migration_rate,
+        fitness_aggregation,
+        auto_targets: parse_auto_targets(options_table)?
+    })
+}
+
+pub fn options_error(message: String) -> LuaError
+{
+    LuaError::RuntimeError(format!("StartSolve: {}", message))
+}
+
+fn get_option<'lua, T: FromLua<'lua>>(options_table: &LuaTable<'lua>, option_name: &str) -> LuaResult<Option<T>>
+{
+    options_table
+        .get::<&str, Option<T>>(option_name)
+        .map_err(|err| options_error(format!("option '{}' has invalid value: {}", option_name, err)))
+}
+
+// Island takes options of solve which it does not set
+fn parse_island(island_table: &LuaTable, island_number: usize, solve_population_parameters: &PopulationParameters) -> LuaResult<PopulationParameters>
+{
+    let island_error = |message: String| options_error(format!("island {}: {}", island_number, message));
+
+    for option_entry in island_table.clone().pairs::<String, LuaValue>()
+    {
+        let (option_name, _) = option_entry.map_err(|err| island_error(err.to_string()))?;
+
+        if !KNOWN_ISLAND_OPTIONS.contains(&option_name.as_str())
+        {
+            return Err(island_error(format!("unknown option '{}'", option_name)));
+        }
+    }
+
+    let optimizer_type =
+        match get_option::<String>(island_table, "optimizer")? {
+            None => solve_population_parameters.optimizer_type,
+            Some(optimizer_name) => OptimizerType::from_name(&optimizer_name)
+                .ok_or_else(|| island_error(format!("unknown optimizer '{}', expected 'nsga2', 'elitist' or 'steadyState'", optimizer_name)))?
+        };
+
+    let population_size = get_option(island_table, "populationSize")?.unwrap_or(solve_population_parameters.population_size);
+
+    if population_size < 2 || !population_size.is_multiple_of(2)
+    {
+        return Err(island_error(format!("'populationSize' should be an even number greater than 1, got {}", population_size)));
+    }
+
+    let elites_count = get_option(island_table, "elitesCount")?.unwrap_or(solve_population_parameters.elites_count);
+
+    if elites_count >= population_size
+    {
+        return Err(island_error(format!("'elitesCount' should be less than population size {}", population_size)));
+    }
+
+    let tournament_size = get_option(island_table, "tournamentSize")?.unwrap_or(solve_population_parameters.tournament_size);
+
+    if tournament_size == 0
+    {
+        return Err(island_error(String::from("'tournamentSize' should be greater than 0")));
+    }
+
+    Ok(PopulationParameters {
+        optimizer_type,
+        population_size,
+        mutation_odds: get_odds_option(island_table, "mutationOdds", solve_population_parameters.mutation_odds)?,
+        crossover_odds: get_odds_option(island_table, "crossoverOdds", solve_population_parameters.crossover_odds)?,
+        elites_count,
+        tournament_size
+    })
+}
+
+// 'autoTargets' maps names of registry to false, true or a table of settings, other auto targets keep defaults.
+// 'autoTargetConstraints' gives default of constraint for all of them
+fn parse_auto_targets(options_table: &LuaTable) -> LuaResult<Vec<AutoTargetSettings>>
+{
+    let is_constraint = get_option(options_table, "autoTargetConstraints")?.unwrap_or(false);
+
+    let mut auto_targets = get_default_auto_target_settings(is_constraint);
+
+    let auto_targets_table =
+        match get_option::<LuaTable>(options_table, "autoTargets")? {
+            None => return Ok(auto_targets),
+            Some(auto_targets_table) => auto_targets_table
+        };
+
+    for auto_target_entry in auto_targets_table.pairs::<String, LuaValue>()
+    {
+        let (name, value) = auto_target_entry.map_err(|err| options_error(format!("'autoTargets' has invalid entry: {}", err)))?;
+
+        let auto_target_error = |message: String| options_error(format!("auto target '{}': {}", name, message));
+
+        let settings = auto_targets
+            .iter_mut()
+            .find(|settings| settings.name == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = AUTO_TARGETS.iter().map(|(name, _)| *name).collect();
+
+                auto_target_error(format!("unknown auto target, expected one of {}", names.join(", ")))
+            })?;
+
+        match value {
+            LuaValue::Boolean(is_enabled) => settings.is_enabled = is_enabled,
+            LuaValue::Table(settings_table) => {
+                for option_entry in settings_table.clone().pairs::<String, LuaValue>()
+                {
+                    let (option_name, _) = option_entry.map_err(|err| auto_target_error(err.to_string()))?;
+
+                    if !KNOWN_AUTO_TARGET_OPTIONS.contains(&option_name.as_str())
+                    {
+                        return Err(auto_target_error(format!("unknown option '{}'", option_name)));
+                    }
+                }
+
+                settings.is_enabled = get_option(&settings_table, "enabled")?.unwrap_or(settings.is_enabled);
+                settings.is_constraint = get_option(&settings_table, "constraint")?.unwrap_or(settings.is_constraint);
+
+                let weight: f64 = get_option(&settings_table, "weight")?.unwrap_or(settings.weight);
+
+                if !weight.is_finite() || weight < 0.0
+                {
+                    return Err(auto_target_error(format!("'weight' should be a non-negative number, got {}", weight)));
+                }
+
+                settings.weight = weight;
+            },
+            _ => return Err(auto_target_error(format!("should be a boolean or a table, got {}", value.type_name())))
+        }
+    }
+
+    Ok(auto_targets)
+}
+
+fn parse_initial_population_mix(mix_table: &LuaTable) -> LuaResult<InitialPopulationMix>
+{
+    let get_share = |share_name: &str| -> LuaResult<f64> {
+        let share = mix_table
+            .get::<&str, Option<f64>>(share_name)
+            .map_err(|err| options_error(format!("'initialPopulationMix' {} share has invalid value: {}", share_name, err)))?
+            .unwrap_or(0.0);
+
+        if !share.is_finite() || share < 0.0
+        {
+            return Err(options_error(format!("'initialPopulationMix' {} share should not be negative, got {}", share_name, share)));
+        }
+
+        Ok(share)
+    };
+
+    let initial_population_mix = InitialPopulationMix {
+        random: get_share("random")?,
+        seeded: get_share("seeded")?,
+        empty: get_share("empty")?
+    };
+
+    if initial_population_mix.random + initial_population_mix.seeded + initial_population_mix.empty <= 0.0
+    {
+        return Err(options_error(String::from("'initialPopulationMix' should have a positive share")));
+    }
+
+    Ok(initial_population_mix)
+}
+
+fn get_odds_option(options_table: &LuaTable, option_name: &str, default_odds: f64) -> LuaResult<f64>
+{
+    let odds = get_option(options_table, option_name)?.unwrap_or(default_odds);
+
+    if !(0.0..=1.0).contains(&odds)
+    {
+        return Err(options_error(format!("'{}' should be in range from 0 to 1, got {}", option_name, odds)));
+    }
+
+    Ok(odds)
+}
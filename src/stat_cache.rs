@@ -0,0 +1,462 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use serde_json::{json, Value};
+use crate::dna_encoder::DnaConvertResult;
+
+// Version 2 keeps one entry in each line, so saves append changed entries only
+const STAT_CACHE_VERSION: u64 = 2;
+
+// Share of entries which are evicted at once, so eviction does not sort entries on every new tree
+const EVICTED_ENTRIES_DIVISOR: usize = 10;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// Actor and stat name
+pub type StatKey = (String, String);
+
+#[derive(Clone)]
+pub struct StatCacheSettings
+{
+    pub directory: String,
+    pub build_key: String,
+    pub tree_version: String,
+    pub max_entries_count: usize
+}
+
+struct StatCacheEntry
+{
+    stat_values: HashMap<StatKey, Option<f64>>,
+    // Tick of the last hit or change, the least recently used entries are evicted first
+    last_used_tick: AtomicU64
+}
+
+// Entries which are not in file yet
+#[derive(Default)]
+struct PendingSave
+{
+    changed_keys: HashSet<u64>,
+    // File has other version or evicted entries, so it is written again instead of appended
+    is_rewrite_needed: bool,
+    // Lines of file, entries changed between saves take several lines
+    file_lines_count: usize
+}
+
+// Stats of decoded trees, they do not depend on targets so they are kept on disk between sessions.
+// Keys are built with a stable hash, std hasher can change between Rust versions
+pub struct StatCache
+{
+    file_path: String,
+    max_entries_count: usize,
+    entries: RwLock<HashMap<u64, StatCacheEntry>>,
+    pending_save: Mutex<PendingSave>,
+    current_tick: AtomicU64,
+    hits_count: AtomicUsize
+}
+
+impl StatCache
+{
+    // One file for each build configuration and tree version
+    pub fn load(settings: &StatCacheSettings) -> Result<StatCache, String>
+    {
+        let file_hash = stable_hash(settings.build_key.as_bytes(), stable_hash(settings.tree_version.as_bytes(), FNV_OFFSET_BASIS));
+
+        let file_path = Path::new(&settings.directory)
+            .join(format!("stat_cache_{:016x}.json", file_hash))
+            .to_string_lossy()
+            .to_string();
+
+        let mut entries = HashMap::new();
+        let mut pending_save = PendingSave {
+            is_rewrite_needed: true,
+            ..PendingSave::default()
+        };
+
+        if Path::new(&file_path).exists()
+        {
+            let content = fs::read_to_string(&file_path).map_err(|err| format!("Cannot read stat cache {}: {}", file_path, err))?;
+
+            let mut lines = content.lines();
+
+            let header: Value =
+                match lines.next() {
+                    None => Value::Null,
+                    Some(line) => serde_json::from_str(line).map_err(|err| format!("Stat cache {} is corrupted: {}", file_path, err))?
+                };
+
+            // Cache of other format is dropped, it is rebuilt by the next solves
+            if header["version"].as_u64() == Some(STAT_CACHE_VERSION)
+            {
+                pending_save.is_rewrite_needed = false;
+                pending_save.file_lines_count = 1;
+
+                // Later lines are newer, they are merged into earlier ones and are used more recently
+                for (line_index, line) in lines.enumerate()
+                {
+                    let (key, stat_values) = entry_from_json(line).map_err(|err| format!("Stat cache {} is corrupted: {}", file_path, err))?;
+
+                    let entry = entries.entry(key).or_insert_with(|| StatCacheEntry {
+                        stat_values: HashMap::new(),
+                        last_used_tick: AtomicU64::new(0)
+                    });
+
+                    entry.stat_values.extend(stat_values);
+                    entry.last_used_tick = AtomicU64::new(line_index as u64);
+
+                    pending_save.file_lines_count += 1;
+                }
+            }
+        }
+
+        let current_tick = pending_save.file_lines_count as u64;
+
+        let stat_cache = StatCache {
+            file_path,
+            max_entries_count: settings.max_entries_count,
+            entries: RwLock::new(entries),
+            pending_save: Mutex::new(pending_save),
+            current_tick: AtomicU64::new(current_tick),
+            hits_count: AtomicUsize::new(0)
+        };
+
+        // Limit can be lowered since the file was written
+        stat_cache.evict_least_recently_used(&mut stat_cache.pending_save.lock().unwrap(), &mut stat_cache.entries.write().unwrap());
+
+        Ok(stat_cache)
+    }
+
+    pub fn save(&self) -> Result<(), String>
+    {
+        let mut pending_save = self.pending_save.lock().unwrap();
+
+        let entries = self.entries.read().unwrap();
+
+        // Repeated changes of the same entries would grow file without limit
+        if pending_save.file_lines_count > 2 * (entries.len() + 1)
+        {
+            pending_save.is_rewrite_needed = true;
+        }
+
+        if pending_save.is_rewrite_needed
+        {
+            let mut keys: Vec<&u64> = entries.keys().collect();
+
+            // Least recently used go first, so they are the oldest ones on load
+            keys.sort_by_key(|key| entries[*key].last_used_tick.load(Ordering::Relaxed));
+
+            let mut content = header_to_json().to_string();
+
+            for key in &keys
+            {
+                content.push('\n');
+                content.push_str(&entry_to_json(**key, &entries[*key].stat_values).to_string());
+            }
+
+            content.push('\n');
+
+            fs::write(&self.file_path, content).map_err(|err| format!("Cannot write stat cache {}: {}", self.file_path, err))?;
+
+            pending_save.is_rewrite_needed = false;
+            pending_save.file_lines_count = keys.len() + 1;
+            pending_save.changed_keys.clear();
+
+            return Ok(());
+        }
+
+        if pending_save.changed_keys.is_empty()
+        {
+            return Ok(());
+        }
+
+        let mut content = String::new();
+
+        for key in &pending_save.changed_keys
+        {
+            // Evicted after change
+            if let Some(entry) = entries.get(key)
+            {
+                content.push_str(&entry_to_json(*key, &entry.stat_values).to_string());
+                content.push('\n');
+            }
+        }
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&self.file_path)
+            .map_err(|err| format!("Cannot write stat cache {}: {}", self.file_path, err))?;
+
+        // Partly written line would corrupt the file, so it is written again
+        if let Err(err) = file.write_all(content.as_bytes())
+        {
+            pending_save.is_rewrite_needed = true;
+
+            return Err(format!("Cannot write stat cache {}: {}", self.file_path, err));
+        }
+
+        pending_save.file_lines_count += pending_save.changed_keys.len();
+        pending_save.changed_keys.clear();
+
+        Ok(())
+    }
+
+    pub fn get_key(dna_convert_result: &DnaConvertResult) -> u64
+    {
+        let mut hash = FNV_OFFSET_BASIS;
+
+        for node_id in &dna_convert_result.allocated_node_ids
+        {
+            hash = stable_hash(&node_id.to_le_bytes(), hash);
+        }
+
+        // Separates nodes from masteries, so different trees cannot give the same sequence
+        hash = stable_hash(&[0xff], hash);
+
+        for (node_id, effect_id) in &dna_convert_result.selected_mastery_effects
+        {
+            hash = stable_hash(&node_id.to_le_bytes(), hash);
+            hash = stable_hash(&effect_id.to_le_bytes(), hash);
+        }
+
+        hash
+    }
+
+    pub fn get_stats(&self, key: u64) -> Option<HashMap<StatKey, Option<f64>>>
+    {
+        let entries = self.entries.read().unwrap();
+
+        let entry = entries.get(&key)?;
+
+        entry.last_used_tick.store(self.next_tick(), Ordering::Relaxed);
+
+        Some(entry.stat_values.clone())
+    }
+
+    pub fn add_hit(&self)
+    {
+        self.hits_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Stats read by new targets are added to stats of previous sessions
+    pub fn merge_stats(&self, key: u64, stat_values: &HashMap<StatKey, Option<f64>>)
+    {
+        // Locks are taken in the same order as in save
+        let mut pending_save = self.pending_save.lock().unwrap();
+        let mut entries = self.entries.write().unwrap();
+
+        let entry = entries.entry(key).or_insert_with(|| StatCacheEntry {
+            stat_values: HashMap::new(),
+            last_used_tick: AtomicU64::new(0)
+        });
+
+        for (stat_key, stat_value) in stat_values
+        {
+            entry.stat_values.insert(stat_key.clone(), *stat_value);
+        }
+
+        entry.last_used_tick.store(self.next_tick(), Ordering::Relaxed);
+
+        pending_save.changed_keys.insert(key);
+
+        self.evict_least_recently_used(&mut pending_save, &mut entries);
+    }
+
+    pub fn get_hits_count(&self) -> usize
+    {
+        self.hits_count.load(Ordering::Relaxed)
+    }
+
+    pub fn get_entries_count(&self) -> usize
+    {
+        self.entries.read().unwrap().len()
+    }
+
+    fn next_tick(&self) -> u64
+    {
+        self.current_tick.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn evict_least_recently_used(&self, pending_save: &mut PendingSave, entries: &mut HashMap<u64, StatCacheEntry>)
+    {
+        if entries.len() <= self.max_entries_count
+        {
+            return;
+        }
+
+        let kept_entries_count = self.max_entries_count - self.max_entries_count / EVICTED_ENTRIES_DIVISOR;
+
+        let mut ticks: Vec<(u64, u64)> = entries
+            .iter()
+            .map(|(key, entry)| (entry.last_used_tick.load(Ordering::Relaxed), *key))
+            .collect();
+
+        ticks.sort_unstable();
+
+        for (_, key) in &ticks[..ticks.len() - kept_entries_count]
+        {
+            entries.remove(key);
+        }
+
+        pending_save.is_rewrite_needed = true;
+    }
+}
+
+// FNV-1a
+fn stable_hash(bytes: &[u8], mut hash: u64) -> u64
+{
+    for byte in bytes
+    {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+
+    hash
+}
+
+fn header_to_json() -> Value
+{
+    json!({
+        "version": STAT_CACHE_VERSION
+    })
+}
+
+fn entry_to_json(key: u64, stat_values: &HashMap<StatKey, Option<f64>>) -> Value
+{
+    let stats_json: Vec<Value> = stat_values
+        .iter()
+        .map(|((actor, stat), stat_value)| json!([actor, stat, stat_value]))
+        .collect();
+
+    json!({
+        "key": key,
+        "stats": stats_json
+    })
+}
+
+fn entry_from_json(line: &str) -> Result<(u64, HashMap<StatKey, Option<f64>>), String>
+{
+    let entry_value: Value = serde_json::from_str(line).map_err(|err| err.to_string())?;
+
+    let key = entry_value["key"].as_u64().ok_or("key is not found")?;
+
+    let mut stat_values = HashMap::new();
+
+    for stat_value in entry_value["stats"].as_array().ok_or("stats is not found")?
+    {
+        let actor = stat_value[0].as_str().ok_or("stat actor is not found")?;
+        let stat = stat_value[1].as_str().ok_or("stat name is not found")?;
+
+        stat_values.insert((actor.to_string(), stat.to_string()), stat_value[2].as_f64());
+    }
+
+    Ok((key, stat_values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_convert_result(allocated_node_ids: Vec<i64>, selected_mastery_effects: Vec<(i64, i64)>) -> DnaConvertResult
+    {
+        DnaConvertResult {
+            allocated_normal_nodes: allocated_node_ids.len(),
+            allocated_ascend_nodes: 0,
+            allocated_node_ids,
+            selected_mastery_effects,
+            unreachable_node_indexes: vec![]
+        }
+    }
+
+    fn create_settings(test_name: &str, max_entries_count: usize) -> StatCacheSettings
+    {
+        let directory = std::env::temp_dir().join(format!("stat_cache_test_{}_{}", test_name, std::process::id()));
+
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        StatCacheSettings {
+            directory: directory.to_string_lossy().to_string(),
+            build_key: String::from("build"),
+            tree_version: String::from("3_25"),
+            max_entries_count
+        }
+    }
+
+    fn create_stat_values(value: f64) -> HashMap<StatKey, Option<f64>>
+    {
+        HashMap::from([((String::from("player"), String::from("Life")), Some(value))])
+    }
+
+    #[test]
+    fn hash_is_fnv1a() {
+        assert_eq!(stable_hash(b"", FNV_OFFSET_BASIS), FNV_OFFSET_BASIS);
+        assert_eq!(stable_hash(b"a", FNV_OFFSET_BASIS), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn key_depends_on_decoded_tree_only() {
+        let key = StatCache::get_key(&create_convert_result(vec![1, 2], vec![(2, 7)]));
+
+        assert_eq!(key, StatCache::get_key(&create_convert_result(vec![1, 2], vec![(2, 7)])));
+        assert_ne!(key, StatCache::get_key(&create_convert_result(vec![1, 2], vec![(2, 8)])));
+        assert_ne!(key, StatCache::get_key(&create_convert_result(vec![1, 2, 7], vec![])));
+    }
+
+    #[test]
+    fn appended_entries_are_loaded() {
+        let settings = create_settings("append", 10);
+
+        let stat_cache = StatCache::load(&settings).unwrap();
+
+        stat_cache.merge_stats(1, &create_stat_values(10.0));
+        stat_cache.save().unwrap();
+
+        stat_cache.merge_stats(2, &create_stat_values(20.0));
+        stat_cache.merge_stats(1, &HashMap::from([((String::from("player"), String::from("Mana")), None)]));
+        stat_cache.save().unwrap();
+
+        let loaded_stat_cache = StatCache::load(&settings).unwrap();
+
+        assert_eq!(loaded_stat_cache.get_entries_count(), 2);
+        assert_eq!(loaded_stat_cache.get_stats(2), Some(create_stat_values(20.0)));
+
+        let stat_values = loaded_stat_cache.get_stats(1).unwrap();
+
+        assert_eq!(stat_values.len(), 2);
+        assert_eq!(stat_values[&(String::from("player"), String::from("Life"))], Some(10.0));
+
+        let _ = fs::remove_dir_all(&settings.directory);
+    }
+
+    #[test]
+    fn least_recently_used_entries_are_evicted() {
+        let settings = create_settings("evict", 10);
+
+        let stat_cache = StatCache::load(&settings).unwrap();
+
+        for key in 0..10
+        {
+            stat_cache.merge_stats(key, &create_stat_values(key as f64));
+        }
+
+        assert!(stat_cache.get_stats(0).is_some());
+
+        stat_cache.merge_stats(10, &create_stat_values(10.0));
+
+        assert_eq!(stat_cache.get_entries_count(), 9);
+        assert!(stat_cache.get_stats(0).is_some());
+        assert!(stat_cache.get_stats(1).is_none());
+        assert!(stat_cache.get_stats(2).is_none());
+        assert!(stat_cache.get_stats(10).is_some());
+
+        stat_cache.save().unwrap();
+
+        assert_eq!(StatCache::load(&settings).unwrap().get_entries_count(), 9);
+
+        let _ = fs::remove_dir_all(&settings.directory);
+    }
+}
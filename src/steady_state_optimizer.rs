@@ -0,0 +1,196 @@
+use rand::prelude::StdRng;
+use rand::{Rng, SeedableRng};
+use sss_moo::evaluator::Evaluator;
+use sss_moo::{Meta, Ratio, Solution, SolutionsRuntimeProcessor};
+
+use crate::pareto::cmp_objective_values;
+
+// Processor which evaluates candidates one by one, results come back as soon as workers return them
+pub trait SteadyStateProcessor<S: Solution>: SolutionsRuntimeProcessor<S> {
+    // Does not wait for evaluation
+    fn send_candidate(&mut self, candidate: S);
+
+    // Waits for any sent candidate, None when nobody is left to evaluate candidates
+    fn receive_candidate(&mut self) -> Option<S>;
+
+    // Count of candidates which can be evaluated at the same time
+    fn parallel_evaluations_count(&self) -> usize;
+}
+
+#[derive(Clone)]
+struct Candidate<S: Solution> {
+    sol: S,
+    value: f64,
+}
+
+// Steady-state GA on the first objective of meta: every returned offspring replaces the worst candidate
+// if it is not worse, and a new offspring is sent right away, so workers do not wait for the slowest one.
+// Generation is population size of evaluations, stop criteria and stagnation count such generations
+pub struct SteadyStateOptimizer<'a, S: Solution> {
+    meta: Box<dyn Meta<'a, S> + 'a>,
+    rng: StdRng,
+    tournament_size: usize,
+}
+
+impl<'a, S> SteadyStateOptimizer<'a, S>
+    where
+        S: Solution,
+{
+    pub fn new(meta: impl Meta<'a, S> + 'a, seed: u64, tournament_size: usize) -> Self {
+        SteadyStateOptimizer {
+            meta: Box::new(meta),
+            rng: StdRng::seed_from_u64(seed),
+            tournament_size,
+        }
+    }
+
+    pub fn optimize(&mut self, eval: &mut Box<dyn Evaluator>, runtime_solutions_processor: &mut impl SteadyStateProcessor<S>) {
+        let pop_size = self.meta.population_size();
+
+        let mut pop: Vec<S> = (0..pop_size)
+            .map(|_| self.meta.random_solution())
+            .collect();
+
+        runtime_solutions_processor.new_candidates(pop.iter_mut().collect());
+
+        let mut parent_pop = self.sort(pop);
+
+        let max_pending_count = runtime_solutions_processor.parallel_evaluations_count().clamp(1, pop_size);
+        let mut pending_count = 0;
+
+        for iter in 0.. {
+            if runtime_solutions_processor.needs_early_stop()
+            {
+                break;
+            }
+
+            runtime_solutions_processor.iteration_num(iter);
+
+            runtime_solutions_processor.iter_solutions(
+                parent_pop.iter_mut()
+                    .map(|candidate| &mut candidate.sol)
+                    .collect()
+            );
+
+            // Processor can replace candidates, for example by migration
+            parent_pop = self.sort(parent_pop.into_iter().map(|candidate| candidate.sol).collect());
+
+            if self.meta.objectives()[0].good_enough(parent_pop[0].value)
+            {
+                break;
+            }
+
+            if eval.can_terminate(iter, parent_pop.iter().map(|candidate| vec![candidate.value]).collect())
+            {
+                break;
+            }
+
+            let mut received_count = 0;
+
+            while received_count < pop_size {
+                while pending_count < max_pending_count {
+                    let child = self.breed(&parent_pop);
+
+                    runtime_solutions_processor.send_candidate(child);
+                    pending_count += 1;
+                }
+
+                let child = match runtime_solutions_processor.receive_candidate() {
+                    Some(child) => child,
+                    None => return
+                };
+
+                pending_count -= 1;
+                received_count += 1;
+
+                self.replace_worst(&mut parent_pop, child);
+
+                // Time limit and stop request should not wait for the whole generation
+                if runtime_solutions_processor.needs_early_stop()
+                {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn breed(&mut self, pop: &[Candidate<S>]) -> S {
+        let mut child = self.tournament(pop).clone();
+
+        let is_crossed = self.odds(self.meta.crossover_odds());
+
+        if is_crossed {
+            let mut other = self.tournament(pop).clone();
+
+            child.crossover(&mut other);
+        }
+
+        // Unchanged copy of parent would waste evaluation
+        if !is_crossed || self.odds(self.meta.mutation_odds()) {
+            child.mutate();
+        }
+
+        child
+    }
+
+    // Equal child replaces the worst candidate too, so population can drift on plateaus
+    fn replace_worst(&self, pop: &mut Vec<Candidate<S>>, child: S) {
+        let value = self.value(&child);
+
+        if pop.last().is_some_and(|worst| value > worst.value)
+        {
+            return;
+        }
+
+        pop.pop();
+
+        let position = pop.partition_point(|candidate| candidate.value <= value);
+
+        pop.insert(position, Candidate {
+            sol: child,
+            value
+        });
+    }
+
+    fn odds(&mut self, ratio: &Ratio) -> bool {
+        self.rng.gen_ratio(ratio.0, ratio.1)
+    }
+
+    // Population is sorted, so the smallest of random indexes is the best candidate
+    fn tournament<'b>(&mut self, pop: &'b [Candidate<S>]) -> &'b S {
+        let winner_index = (0..self.tournament_size)
+            .map(|_| self.rng.gen_range(0..pop.len()))
+            .min()
+            .unwrap_or(0);
+
+        &pop[winner_index].sol
+    }
+
+    // Best candidate first, objective values are minimized
+    fn sort(&self, pop: Vec<S>) -> Vec<Candidate<S>> {
+        let mut candidates: Vec<Candidate<S>> = pop
+            .into_iter()
+            .map(|sol| {
+                let value = self.value(&sol);
+
+                Candidate {
+                    sol,
+                    value
+                }
+            })
+            .collect();
+
+        candidates.sort_by(|a, b| cmp_objective_values(a.value, b.value));
+
+        candidates
+    }
+
+    fn value(&self, s: &S) -> f64 {
+        let objective = &self.meta.objectives()[0];
+
+        self.meta
+            .constraints()
+            .iter()
+            .fold(objective.value(s), |acc, cons| cons.value(s, acc))
+    }
+}
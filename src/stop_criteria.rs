@@ -0,0 +1,100 @@
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use sss_moo::evaluator::{DefaultEvaluator, Evaluator};
+use crate::pob_solver::ProcessStatus;
+
+// Multiplier of a target which value is reached, see FitnessFunctionCalculator::calc_target_mul
+pub const TARGET_MET_FITNESS_SCORE: f64 = 1.0 - 1e-9;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StopReason
+{
+    Stagnation,
+    StopRequest,
+    TimeLimit,
+    EvaluationsLimit,
+    GenerationsLimit,
+    TargetsMet,
+    WorkersFailed,
+    SolverFailed
+}
+
+impl StopReason
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            StopReason::Stagnation => "stagnation",
+            StopReason::StopRequest => "stopRequest",
+            StopReason::TimeLimit => "timeLimit",
+            StopReason::EvaluationsLimit => "evaluationsLimit",
+            StopReason::GenerationsLimit => "generationsLimit",
+            StopReason::TargetsMet => "targetsMet",
+            StopReason::WorkersFailed => "workersFailed",
+            StopReason::SolverFailed => "solverFailed"
+        }
+    }
+}
+
+// Optional stop conditions, a run ends on the first one that is reached.
+// Stagnation through stop_generations_eps is always active
+#[derive(Clone, Default)]
+pub struct StopCriteria
+{
+    pub time_limit: Option<Duration>,
+    pub max_evaluations: Option<usize>,
+    pub max_generations: Option<usize>,
+    pub is_stop_when_targets_met: bool
+}
+
+pub fn is_targets_met(fitness_score_targets: &[f64], required_target_indexes: &[usize]) -> bool
+{
+    required_target_indexes
+        .iter()
+        .all(|target_index| fitness_score_targets[*target_index] >= TARGET_MET_FITNESS_SCORE)
+}
+
+// Stops on stagnation, or right after runtime processor has decided to stop
+// Stagnated island stops alone, solve stops by stagnation when the last running population stagnates
+pub struct SolveEvaluator
+{
+    stagnation_evaluator: DefaultEvaluator,
+    process_status: Arc<RwLock<ProcessStatus>>,
+    running_populations_count: Arc<AtomicUsize>
+}
+
+impl SolveEvaluator
+{
+    pub fn new(stop_generations_eps: usize, process_status: Arc<RwLock<ProcessStatus>>, running_populations_count: Arc<AtomicUsize>) -> Self
+    {
+        SolveEvaluator {
+            stagnation_evaluator: DefaultEvaluator::new(stop_generations_eps),
+            process_status,
+            running_populations_count
+        }
+    }
+}
+
+impl Evaluator for SolveEvaluator
+{
+    fn can_terminate(&mut self, iter: usize, values: Vec<Vec<f64>>) -> bool
+    {
+        if self.process_status.read().unwrap().stop_reason.is_some()
+        {
+            return true;
+        }
+
+        if self.stagnation_evaluator.can_terminate(iter, values)
+        {
+            if self.running_populations_count.fetch_sub(1, Ordering::SeqCst) == 1
+            {
+                self.process_status.write().unwrap().stop_reason = Some(StopReason::Stagnation);
+            }
+
+            return true;
+        }
+
+        false
+    }
+}
@@ -1,10 +1,23 @@
+use serde_json::Value;
+use mlua::prelude::LuaResult;
+use crate::auto_targets::{AutoTargetFromStatToStat, AutoTargetManaCost, AutoTargetManaRegen};
 use crate::fitness_function_calculator::{FitnessFunctionCalculator, FitnessFunctionCalculatorStats};
+use crate::user_target::UserTarget;
 
 pub trait Target: Send + Sync
 {
     fn clone_dyn(&self) -> Box<dyn Target>;
-    fn calc_fitness_score(&self, fitness_function_calculator: &FitnessFunctionCalculator, stats: &mut FitnessFunctionCalculatorStats) -> f64;
-    fn get_maximize_value(&self, stats: &mut FitnessFunctionCalculatorStats) -> f64;
+    fn calc_fitness_score(&self, fitness_function_calculator: &FitnessFunctionCalculator, stats: &mut FitnessFunctionCalculatorStats) -> LuaResult<f64>;
+    fn get_maximize_value(&self, stats: &mut FitnessFunctionCalculatorStats) -> LuaResult<f64>;
+    // Power or multiplier of target score in fitness score, see FitnessAggregation
+    fn get_weight(&self) -> f64;
+    fn to_json(&self) -> Value;
+    // Target with a value that should be reached, not maximized
+    fn is_user_requirement(&self) -> bool;
+    // Dna which misses the target is ranked below all dnas which reach it, see TargetsConstraint
+    fn is_constraint(&self) -> bool;
+    // Name in registry of auto targets, None for user targets
+    fn get_auto_target_name(&self) -> Option<&str>;
 }
 
 impl Clone for Box<dyn Target> {
@@ -12,3 +25,22 @@ impl Clone for Box<dyn Target> {
         self.clone_dyn()
     }
 }
+
+pub fn create_target_from_json(value: &Value) -> Result<Box<dyn Target>, String>
+{
+    let target_type = value["type"].as_str().ok_or("Target type is not found")?;
+
+    match target_type {
+        "user" => Ok(Box::new(UserTarget::from_json(value)?)),
+        "autoManaCost" => Ok(Box::new(AutoTargetManaCost{
+            weight: value["weight"].as_f64().unwrap_or(1.0),
+            is_constraint: value["isConstraint"].as_bool().unwrap_or(false)
+        })),
+        "autoManaRegen" => Ok(Box::new(AutoTargetManaRegen{
+            weight: value["weight"].as_f64().unwrap_or(1.0),
+            is_constraint: value["isConstraint"].as_bool().unwrap_or(false)
+        })),
+        "autoFromStatToStat" => Ok(Box::new(AutoTargetFromStatToStat::from_json(value)?)),
+        _ => Err(format!("Unknown target type: {}", target_type))
+    }
+}
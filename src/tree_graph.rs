@@ -0,0 +1,268 @@
+use serde_json::{json, Value};
+use crate::dna::MAX_MASTERY_EFFECTS_COUNT;
+
+// Links of tree nodes by dna gene indexes, taken from DnaEncoder so genetic operators can follow the tree
+pub struct TreeGraph
+{
+    pub linked_indexes: Vec<Vec<usize>>,
+    // Nodes allocated without dna, class starts
+    pub root_indexes: Vec<usize>,
+    pub mastery_effects_counts: Vec<usize>
+}
+
+impl TreeGraph
+{
+    // Nodes which are not selected, but are linked to selected ones or to roots
+    pub fn get_frontier_indexes(&self, body_nodes: &[u8]) -> Vec<usize>
+    {
+        let mut is_frontier = vec![false; self.linked_indexes.len()];
+
+        let selected_indexes = body_nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, nucl)| **nucl == 1)
+            .map(|(node_index, _)| node_index);
+
+        for node_index in selected_indexes.chain(self.root_indexes.iter().cloned())
+        {
+            for linked_index in &self.linked_indexes[node_index]
+            {
+                if body_nodes[*linked_index] == 0 && !self.root_indexes.contains(linked_index)
+                {
+                    is_frontier[*linked_index] = true;
+                }
+            }
+        }
+
+        is_frontier
+            .iter()
+            .enumerate()
+            .filter(|(_, is_frontier)| **is_frontier)
+            .map(|(node_index, _)| node_index)
+            .collect()
+    }
+
+    // Selected node linked with no more than one selected node or root, removing it does not split the tree
+    pub fn is_leaf(&self, body_nodes: &[u8], node_index: usize) -> bool
+    {
+        body_nodes[node_index] == 1
+            && self.linked_indexes[node_index]
+                .iter()
+                .filter(|linked_index| body_nodes[**linked_index] == 1 || self.root_indexes.contains(linked_index))
+                .count() <= 1
+    }
+
+    pub fn get_leaf_indexes(&self, body_nodes: &[u8]) -> Vec<usize>
+    {
+        (0..body_nodes.len())
+            .filter(|node_index| self.is_leaf(body_nodes, *node_index))
+            .collect()
+    }
+
+    // Count of links from the closest root, usize::MAX for nodes which cannot be reached
+    pub fn get_root_distances(&self) -> Vec<usize>
+    {
+        let mut root_distances = vec![usize::MAX; self.linked_indexes.len()];
+        let mut queue_indexes = self.root_indexes.clone();
+
+        for root_index in &self.root_indexes
+        {
+            root_distances[*root_index] = 0;
+        }
+
+        let mut next_position = 0;
+
+        while next_position < queue_indexes.len()
+        {
+            let node_index = queue_indexes[next_position];
+            next_position += 1;
+
+            for linked_index in &self.linked_indexes[node_index]
+            {
+                if root_distances[*linked_index] == usize::MAX
+                {
+                    root_distances[*linked_index] = root_distances[node_index] + 1;
+                    queue_indexes.push(*linked_index);
+                }
+            }
+        }
+
+        root_distances
+    }
+
+    // Nodes closest to center by links, center included
+    pub fn get_ball_indexes(&self, center_index: usize, max_nodes_count: usize) -> Vec<usize>
+    {
+        let mut is_visited = vec![false; self.linked_indexes.len()];
+        let mut ball_indexes = vec![center_index];
+
+        is_visited[center_index] = true;
+
+        let mut next_position = 0;
+
+        while next_position < ball_indexes.len() && ball_indexes.len() < max_nodes_count
+        {
+            let node_index = ball_indexes[next_position];
+            next_position += 1;
+
+            for linked_index in &self.linked_indexes[node_index]
+            {
+                if !is_visited[*linked_index] && ball_indexes.len() < max_nodes_count
+                {
+                    is_visited[*linked_index] = true;
+                    ball_indexes.push(*linked_index);
+                }
+            }
+        }
+
+        ball_indexes
+    }
+
+    // Connected groups of nodes marked by is_included, links to not included nodes are ignored
+    pub fn get_components(&self, is_included: &[bool]) -> Vec<Vec<usize>>
+    {
+        let mut is_visited = vec![false; self.linked_indexes.len()];
+        let mut components = Vec::new();
+
+        for start_index in 0..is_included.len()
+        {
+            if !is_included[start_index] || is_visited[start_index]
+            {
+                continue;
+            }
+
+            is_visited[start_index] = true;
+
+            let mut component = vec![start_index];
+            let mut next_position = 0;
+
+            while next_position < component.len()
+            {
+                let node_index = component[next_position];
+                next_position += 1;
+
+                for linked_index in &self.linked_indexes[node_index]
+                {
+                    if is_included[*linked_index] && !is_visited[*linked_index]
+                    {
+                        is_visited[*linked_index] = true;
+                        component.push(*linked_index);
+                    }
+                }
+            }
+
+            components.push(component);
+        }
+
+        components
+    }
+
+    pub fn get_mastery_effects_count(&self, mastery_index: usize) -> usize
+    {
+        self.mastery_effects_counts[mastery_index].min(MAX_MASTERY_EFFECTS_COUNT)
+    }
+
+    pub fn to_json(&self) -> Value
+    {
+        json!({
+            "linkedIndexes": self.linked_indexes,
+            "rootIndexes": self.root_indexes,
+            "masteryEffectsCounts": self.mastery_effects_counts
+        })
+    }
+
+    pub fn from_json(value: &Value, tree_nodes_count: usize) -> Result<TreeGraph, String>
+    {
+        let get_indexes = |indexes_value: &Value| -> Result<Vec<usize>, String> {
+            indexes_value
+                .as_array()
+                .ok_or("Tree graph indexes are not found")?
+                .iter()
+                .map(|index_value| match index_value.as_u64() {
+                    Some(index) if (index as usize) < tree_nodes_count => Ok(index as usize),
+                    _ => Err(String::from("Tree graph contains index out of tree"))
+                })
+                .collect()
+        };
+
+        let linked_indexes = value["linkedIndexes"]
+            .as_array()
+            .ok_or("linkedIndexes is not found")?
+            .iter()
+            .map(get_indexes)
+            .collect::<Result<Vec<Vec<usize>>, String>>()?;
+
+        if linked_indexes.len() != tree_nodes_count
+        {
+            return Err(String::from("Tree graph does not match tree nodes count"));
+        }
+
+        let mastery_effects_counts = value["masteryEffectsCounts"]
+            .as_array()
+            .ok_or("masteryEffectsCounts is not found")?
+            .iter()
+            .map(|count_value| count_value.as_u64().map(|count| count as usize).ok_or(String::from("Invalid mastery effects count")))
+            .collect::<Result<Vec<usize>, String>>()?;
+
+        Ok(TreeGraph {
+            linked_indexes,
+            root_indexes: get_indexes(&value["rootIndexes"])?,
+            mastery_effects_counts
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Root 0 - 1 - 2 - 3, and 1 - 4
+    fn create_tree_graph() -> TreeGraph
+    {
+        TreeGraph {
+            linked_indexes: vec![vec![1], vec![0, 2, 4], vec![1, 3], vec![2], vec![1]],
+            root_indexes: vec![0],
+            mastery_effects_counts: vec![]
+        }
+    }
+
+    #[test]
+    fn frontier_is_linked_to_selected_nodes_or_roots() {
+        let tree_graph = create_tree_graph();
+
+        assert_eq!(tree_graph.get_frontier_indexes(&[0, 0, 0, 0, 0]), vec![1]);
+        assert_eq!(tree_graph.get_frontier_indexes(&[0, 1, 0, 0, 0]), vec![2, 4]);
+        assert_eq!(tree_graph.get_frontier_indexes(&[0, 1, 1, 0, 1]), vec![3]);
+    }
+
+    #[test]
+    fn leaves_do_not_split_tree() {
+        let tree_graph = create_tree_graph();
+
+        assert_eq!(tree_graph.get_leaf_indexes(&[0, 1, 1, 0, 1]), vec![2, 4]);
+        assert!(!tree_graph.is_leaf(&[0, 1, 1, 0, 1], 1));
+    }
+
+    #[test]
+    fn root_distances_count_links() {
+        assert_eq!(create_tree_graph().get_root_distances(), vec![0, 1, 2, 3, 2]);
+    }
+
+    #[test]
+    fn components_ignore_links_to_excluded_nodes() {
+        let tree_graph = create_tree_graph();
+
+        assert_eq!(tree_graph.get_components(&[false, true, false, true, true]), vec![vec![1, 4], vec![3]]);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let tree_graph = create_tree_graph();
+
+        let loaded_tree_graph = TreeGraph::from_json(&tree_graph.to_json(), 5).unwrap();
+
+        assert_eq!(loaded_tree_graph.linked_indexes, tree_graph.linked_indexes);
+        assert_eq!(loaded_tree_graph.root_indexes, tree_graph.root_indexes);
+        assert!(TreeGraph::from_json(&tree_graph.to_json(), 4).is_err());
+    }
+}
@@ -1,5 +1,6 @@
-use mlua::{Lua, Table};
-use mlua::prelude::{LuaTable, LuaValue};
+use mlua::{FromLua, Lua, Table};
+use mlua::prelude::{LuaError, LuaResult, LuaTable, LuaValue};
+use serde_json::{json, Value};
 use crate::fitness_function_calculator::{FitnessFunctionCalculator, FitnessFunctionCalculatorStats};
 use crate::target::Target;
 
@@ -12,7 +13,8 @@ pub struct UserTarget
     pub weight: f64,
     pub target: f64,
     pub is_maximize: bool,
-    pub lower_is_better: bool
+    pub lower_is_better: bool,
+    pub is_constraint: bool
 }
 
 impl Target for UserTarget
@@ -21,17 +23,17 @@ impl Target for UserTarget
         Box::new(self.clone())
     }
 
-    fn calc_fitness_score(&self, fitness_function_calculator: &FitnessFunctionCalculator, stats: &mut FitnessFunctionCalculatorStats) -> f64 {
-        let stat = stats.try_get_stat(self.actor.clone(), self.stat.clone());
+    fn calc_fitness_score(&self, fitness_function_calculator: &FitnessFunctionCalculator, stats: &mut FitnessFunctionCalculatorStats) -> LuaResult<f64> {
+        let stat = stats.try_get_stat(self.actor.clone(), self.stat.clone())?;
 
         if self.is_maximize
         {
             match stat {
                 None => {
-                    0.01
+                    Ok(0.01)
                 }
                 Some(stat_value) => {
-                    stat_value
+                    Ok(stat_value)
                 }
             }
         }
@@ -39,26 +41,26 @@ impl Target for UserTarget
         {
             match stat {
                 None => {
-                    0.01
+                    Ok(0.01)
                 }
                 Some(stat_value) => {
-                    fitness_function_calculator.calc_target_mul(stat_value, self.weight, self.target, self.lower_is_better)
+                    Ok(fitness_function_calculator.calc_target_mul(stat_value, self.target, self.lower_is_better))
                 }
             }
         }
     }
 
-    fn get_maximize_value(&self, stats: &mut FitnessFunctionCalculatorStats) -> f64 {
-        let stat = stats.try_get_stat(self.actor.clone(), self.stat.clone());
+    fn get_maximize_value(&self, stats: &mut FitnessFunctionCalculatorStats) -> LuaResult<f64> {
+        let stat = stats.try_get_stat(self.actor.clone(), self.stat.clone())?;
 
         if self.lower_is_better
         {
             match stat {
                 None => {
-                    0.0
+                    Ok(0.0)
                 }
                 Some(stat_value) => {
-                    -stat_value
+                    Ok(-stat_value)
                 }
             }
         }
@@ -66,67 +68,150 @@ impl Target for UserTarget
         {
             match stat {
                 None => {
-                    0.0
+                    Ok(0.0)
                 }
                 Some(stat_value) => {
-                    stat_value
+                    Ok(stat_value)
                 }
             }
         }
     }
+
+    fn get_weight(&self) -> f64 {
+        self.weight
+    }
+
+    fn is_user_requirement(&self) -> bool {
+        !self.is_maximize
+    }
+
+    fn is_constraint(&self) -> bool {
+        self.is_constraint
+    }
+
+    fn get_auto_target_name(&self) -> Option<&str> {
+        None
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "type": "user",
+            "stat": self.stat,
+            "actor": self.actor,
+            "weight": self.weight,
+            "target": self.target,
+            "isMaximize": self.is_maximize,
+            "lowerIsBetter": self.lower_is_better,
+            "isConstraint": self.is_constraint
+        })
+    }
 }
 
-pub fn create_targets_from_tables(targets_table: LuaTable, maximize_table: LuaTable) -> Vec<UserTarget>
+impl UserTarget
+{
+    pub fn from_json(value: &Value) -> Result<Self, String>
+    {
+        Ok(UserTarget {
+            stat: value["stat"].as_str().ok_or("stat is not found")?.to_string(),
+            actor: value["actor"].as_str().ok_or("actor is not found")?.to_string(),
+            weight: value["weight"].as_f64().ok_or("weight is not found")?,
+            target: value["target"].as_f64().ok_or("target is not found")?,
+            is_maximize: value["isMaximize"].as_bool().ok_or("isMaximize is not found")?,
+            lower_is_better: value["lowerIsBetter"].as_bool().ok_or("lowerIsBetter is not found")?,
+            is_constraint: value["isConstraint"].as_bool().unwrap_or(false)
+        })
+    }
+}
+
+pub fn create_targets_from_tables(targets_table: LuaTable, maximize_table: LuaTable) -> LuaResult<Vec<UserTarget>>
 {
     let mut targets = Vec::new();
 
     for entry_target in targets_table.pairs()
     {
-        let (_, lua_target): (LuaValue, LuaTable) = entry_target.unwrap();
+        let (target_key, lua_target): (LuaValue, LuaValue) = entry_target?;
 
-        let lower_is_better =
-            match lua_target.get::<&str, Option<bool>>("lowerIsBetter").unwrap() {
-                None => false,
-                Some(lower_is_better) => lower_is_better
-            };
+        let lua_target = get_target_table(lua_target, "targets", &target_key)?;
 
         targets.push(UserTarget {
-            stat: lua_target.get("stat").unwrap(),
-            actor: lua_target.get("actor").unwrap(),
-            weight: lua_target.get("weight").unwrap(),
-            target: lua_target.get("target").unwrap(),
+            stat: get_target_field(&lua_target, "targets", &target_key, "stat")?,
+            actor: get_target_field(&lua_target, "targets", &target_key, "actor")?,
+            weight: get_target_weight(&lua_target, "targets", &target_key)?,
+            target: get_target_field(&lua_target, "targets", &target_key, "target")?,
             is_maximize: false,
-            lower_is_better
+            lower_is_better: get_target_field::<Option<bool>>(&lua_target, "targets", &target_key, "lowerIsBetter")?.unwrap_or(false),
+            is_constraint: get_target_field::<Option<bool>>(&lua_target, "targets", &target_key, "constraint")?.unwrap_or(false)
         });
     }
 
     for entry_target in maximize_table.pairs()
     {
-        let (_, lua_target): (LuaValue, LuaTable) = entry_target.unwrap();
+        let (target_key, lua_target): (LuaValue, LuaValue) = entry_target?;
+
+        let lua_target = get_target_table(lua_target, "maximizes", &target_key)?;
 
-        let lower_is_better =
-            match lua_target.get::<&str, Option<bool>>("lowerIsBetter").unwrap() {
-                None => false,
-                Some(lower_is_better) => lower_is_better
-            };
+        // Maximized stat has no value to reach
+        if get_target_field::<Option<bool>>(&lua_target, "maximizes", &target_key, "constraint")?.is_some()
+        {
+            return Err(LuaError::RuntimeError(format!("maximizes[{}].constraint is not supported, only targets can be constraints", target_key_to_string(&target_key))));
+        }
 
         targets.push(UserTarget {
-            stat: lua_target.get("stat").unwrap(),
-            actor: lua_target.get("actor").unwrap(),
-            weight: lua_target.get("weight").unwrap(),
+            stat: get_target_field(&lua_target, "maximizes", &target_key, "stat")?,
+            actor: get_target_field(&lua_target, "maximizes", &target_key, "actor")?,
+            weight: get_target_weight(&lua_target, "maximizes", &target_key)?,
             target: 0.0,
             is_maximize: true,
-            lower_is_better
+            lower_is_better: get_target_field::<Option<bool>>(&lua_target, "maximizes", &target_key, "lowerIsBetter")?.unwrap_or(false),
+            is_constraint: false
         });
     }
 
-    targets
+    Ok(targets)
+}
+
+fn target_key_to_string(target_key: &LuaValue) -> String
+{
+    match target_key {
+        LuaValue::Integer(index) => index.to_string(),
+        LuaValue::Number(index) => index.to_string(),
+        LuaValue::String(name) => name.to_string_lossy().to_string(),
+        _ => String::from(target_key.type_name())
+    }
+}
+
+fn get_target_table<'lua>(lua_target: LuaValue<'lua>, table_name: &str, target_key: &LuaValue) -> LuaResult<LuaTable<'lua>>
+{
+    match lua_target {
+        LuaValue::Table(lua_target) => Ok(lua_target),
+        _ => Err(LuaError::RuntimeError(format!("{}[{}] should be a table, got {}", table_name, target_key_to_string(target_key), lua_target.type_name())))
+    }
+}
+
+fn get_target_field<'lua, T: FromLua<'lua>>(lua_target: &LuaTable<'lua>, table_name: &str, target_key: &LuaValue, field_name: &str) -> LuaResult<T>
+{
+    lua_target
+        .get(field_name)
+        .map_err(|err| LuaError::RuntimeError(format!("{}[{}].{} is missing or invalid: {}", table_name, target_key_to_string(target_key), field_name, err)))
+}
+
+// Weight is a power of target score in product, so it cannot be negative
+fn get_target_weight(lua_target: &LuaTable, table_name: &str, target_key: &LuaValue) -> LuaResult<f64>
+{
+    let weight: f64 = get_target_field(lua_target, table_name, target_key, "weight")?;
+
+    if !weight.is_finite() || weight < 0.0
+    {
+        return Err(LuaError::RuntimeError(format!("{}[{}].weight should be a non-negative number, got {}", table_name, target_key_to_string(target_key), weight)));
+    }
+
+    Ok(weight)
 }
 
-pub fn create_tables_from_targets<'lua>(lua: &'lua Lua, targets: &Vec<UserTarget>) -> (Table<'lua>, Table<'lua>)
+pub fn create_tables_from_targets<'lua>(lua: &'lua Lua, targets: &Vec<UserTarget>) -> LuaResult<(Table<'lua>, Table<'lua>)>
 {
-    let targets_table = lua.create_table().unwrap();
-    let maximizes_table = lua.create_table().unwrap();
+    let targets_table = lua.create_table()?;
+    let maximizes_table = lua.create_table()?;
 
     let mut count_targets = 0;
     let mut count_maximizes = 0;
@@ -135,28 +220,29 @@ pub fn create_tables_from_targets<'lua>(lua: &'lua Lua, targets: &Vec<UserTarget
     {
         if target.is_maximize
         {
-            let maximize_table = lua.create_table().unwrap();
+            let maximize_table = lua.create_table()?;
 
-            maximize_table.set("stat", target.stat.clone()).unwrap();
-            maximize_table.set("weight", target.weight).unwrap();
-            maximize_table.set("actor", target.actor.clone()).unwrap();
+            maximize_table.set("stat", target.stat.clone())?;
+            maximize_table.set("weight", target.weight)?;
+            maximize_table.set("actor", target.actor.clone())?;
 
             count_maximizes += 1;
-            maximizes_table.set(count_maximizes, maximize_table).unwrap();
+            maximizes_table.set(count_maximizes, maximize_table)?;
         }
         else
         {
-            let target_table = lua.create_table().unwrap();
+            let target_table = lua.create_table()?;
 
-            target_table.set("stat", target.stat.clone()).unwrap();
-            target_table.set("weight", target.weight).unwrap();
-            target_table.set("actor", target.actor.clone()).unwrap();
-            target_table.set("target", target.target).unwrap();
+            target_table.set("stat", target.stat.clone())?;
+            target_table.set("weight", target.weight)?;
+            target_table.set("actor", target.actor.clone())?;
+            target_table.set("target", target.target)?;
+            target_table.set("constraint", target.is_constraint)?;
 
             count_targets += 1;
-            targets_table.set(count_targets, target_table).unwrap();
+            targets_table.set(count_targets, target_table)?;
         }
     }
 
-    (targets_table, maximizes_table)
+    Ok((targets_table, maximizes_table))
 }
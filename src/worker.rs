@@ -1,16 +1,23 @@
+use std::any::Any;
 use std::cell::RefCell;
-use std::fs;
+use std::{fs, panic, thread};
+use std::panic::AssertUnwindSafe;
 use std::rc::Rc;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use crossbeam::channel::{Receiver, Sender};
 use mlua::{Function, Lua, LuaOptions, StdLib, UserData};
-use mlua::prelude::{LuaMultiValue, LuaResult, LuaString, LuaTable, LuaValue};
+use mlua::prelude::{LuaError, LuaMultiValue, LuaResult, LuaString, LuaTable, LuaValue};
 use crate::dna::Dna;
+use crate::dna_cache_fitness::DnaCacheFitness;
+use crate::stat_cache::StatCache;
 use crate::dna_encoder::{create_dna_encoder, DnaEncoder};
 use crate::fitness_function_calculator::{FitnessFunctionCalculator, FitnessFunctionCalculatorStats};
 
 use crate::pob_solver::{DnaCommand, Session};
 
+const MAX_WORKER_RESTARTS_COUNT: usize = 10;
+
 #[derive(Clone)]
 pub struct LuaDnaCommand
 {
@@ -24,115 +31,318 @@ struct SessionProcessRuntime
     target_normal_nodes_count: usize,
     target_ascendancy_nodes_count: usize,
     dna_encoder: DnaEncoder,
-    fitness_function_calculator: FitnessFunctionCalculator
+    fitness_function_calculator: FitnessFunctionCalculator,
+    fitness_cache: Arc<DnaCacheFitness>,
+    stat_cache: Option<Arc<StatCache>>
+}
+
+struct WorkerRuntime<'lua>
+{
+    lua_build: LuaTable<'lua>,
+    calculate_stats_func: Function<'lua>,
+    init_session_func: Function<'lua>,
+    stored_session_number: usize,
+    session_process_runtime: Option<SessionProcessRuntime>
+}
+
+// Failures of workers, shared between worker threads and solver
+#[derive(Default)]
+pub struct WorkersStatus
+{
+    pub alive_workers_count: AtomicUsize,
+    pub errors_count: AtomicUsize,
+    pub last_error: Mutex<Option<String>>
+}
+
+impl WorkersStatus
+{
+    fn report_error(&self, error: String)
+    {
+        self.errors_count.fetch_add(1, Ordering::SeqCst);
+
+        *self.last_error.lock().unwrap() = Some(error);
+    }
 }
 
-pub fn worker_main(reader_dna_queue_channel: Receiver<Box<DnaCommand>>,
-                   writer_dna_result_queue_channel: Sender<Box<DnaCommand>>,
-                   session: Arc<RwLock<Session>>,
-                   working_dir: &str
+// Runs worker_main and starts it again with new Lua state when it fails.
+// Worker is given up after MAX_WORKER_RESTARTS_COUNT failures, solver sees it by alive_workers_count
+pub fn spawn_worker(reader_dna_queue_channel: Receiver<Box<DnaCommand>>,
+                    writer_dna_result_queue_channel: Sender<Box<DnaCommand>>,
+                    session: Arc<RwLock<Session>>,
+                    workers_status: Arc<WorkersStatus>,
+                    working_dir: String
 )
 {
-    let lua = Lua::new_with(StdLib::ALL_SAFE, LuaOptions::default()).unwrap();
+    workers_status.alive_workers_count.fetch_add(1, Ordering::SeqCst);
+
+    thread::spawn(move || {
+        let mut restarts_count = 0;
+
+        loop {
+            let worker_result = panic::catch_unwind(AssertUnwindSafe(|| {
+                worker_main(&reader_dna_queue_channel,
+                            &writer_dna_result_queue_channel,
+                            &session,
+                            &workers_status,
+                            &working_dir)
+            }));
+
+            let error =
+                match worker_result {
+                    // Solver is dropped
+                    Ok(Ok(())) => break,
+                    Ok(Err(error)) => error,
+                    Err(panic_payload) => format!("Worker has panicked: {}", panic_message(panic_payload.as_ref()))
+                };
+
+            workers_status.report_error(error);
+
+            restarts_count += 1;
+
+            if restarts_count > MAX_WORKER_RESTARTS_COUNT
+            {
+                break;
+            }
+        }
+
+        workers_status.alive_workers_count.fetch_sub(1, Ordering::SeqCst);
+    });
+}
+
+// Returns Ok when solver is dropped and error when Lua state of worker cannot be used anymore
+fn worker_main(reader_dna_queue_channel: &Receiver<Box<DnaCommand>>,
+               writer_dna_result_queue_channel: &Sender<Box<DnaCommand>>,
+               session: &RwLock<Session>,
+               workers_status: &WorkersStatus,
+               working_dir: &str
+) -> Result<(), String>
+{
+    let lua = Lua::new_with(StdLib::ALL_SAFE, LuaOptions::default())
+        .map_err(|err| format!("Cannot create worker Lua state: {}", err))?;
+
+    let mut worker_runtime = create_worker_runtime(&lua, working_dir)
+        .map_err(|err| format!("Worker initialization is failed: {}", err))?;
+
+    loop {
+        let mut dna_command =
+            match reader_dna_queue_channel.recv() {
+                Ok(dna_command) => dna_command,
+                Err(_) => return Ok(())
+            };
+
+        let evaluate_result = panic::catch_unwind(AssertUnwindSafe(|| {
+            evaluate_dna_command(&mut worker_runtime, session, &mut dna_command)
+        }));
+
+        match evaluate_result {
+            Ok(Ok(())) => {
+                if !send_dna_result(writer_dna_result_queue_channel, dna_command)
+                {
+                    return Ok(());
+                }
+            },
+            Ok(Err(err)) => {
+                let error = format!("Dna evaluation is failed: {}", err);
+
+                workers_status.report_error(error.clone());
+
+                dna_command.error = Some(error);
+
+                if !send_dna_result(writer_dna_result_queue_channel, dna_command)
+                {
+                    return Ok(());
+                }
+            },
+            Err(panic_payload) => {
+                let error = format!("Worker has panicked on dna evaluation: {}", panic_message(panic_payload.as_ref()));
+
+                dna_command.error = Some(error.clone());
+
+                send_dna_result(writer_dna_result_queue_channel, dna_command);
+
+                return Err(error);
+            }
+        }
+    }
+}
 
+// Result goes back to population which sent dna. Returns false when solver is dropped,
+// closed channel of a finished island does not stop worker
+fn send_dna_result(writer_dna_result_queue_channel: &Sender<Box<DnaCommand>>, dna_command: Box<DnaCommand>) -> bool
+{
+    match dna_command.result_channel.clone() {
+        Some(result_channel) => {
+            let _ = result_channel.send(dna_command);
+
+            true
+        },
+        None => writer_dna_result_queue_channel.send(dna_command).is_ok()
+    }
+}
+
+fn create_worker_runtime<'lua>(lua: &'lua Lua, working_dir: &str) -> LuaResult<WorkerRuntime<'lua>>
+{
     let globals = lua.globals();
 
-    let std_io_table = globals.get::<&str, LuaTable>("io").unwrap();
-    let std_io_open_func = std_io_table.get::<&str, Function>("open").unwrap();
+    let std_io_table = globals.get::<&str, LuaTable>("io")?;
+    let std_io_open_func = std_io_table.get::<&str, Function>("open")?;
 
-    std_io_table.set("original_open", std_io_open_func).unwrap();
+    std_io_table.set("original_open", std_io_open_func)?;
 
     let working_dir_io_copy = String::from(working_dir);
     let working_dir_io = lua.create_function(move |lua_context, (file_name, mode): (LuaString, LuaString)| -> LuaResult<LuaMultiValue> {
-        let file_name = working_dir_io_copy.clone() + file_name.to_str().unwrap().to_string().as_str();
+        let file_name = working_dir_io_copy.clone() + file_name.to_str()?;
 
         let globals = lua_context.globals();
 
-        let std_io_table = globals.get::<&str, LuaTable>("io").unwrap();
-        let std_io_open_func = std_io_table.get::<&str, Function>("original_open").unwrap();
+        let std_io_table = globals.get::<&str, LuaTable>("io")?;
+        let std_io_open_func = std_io_table.get::<&str, Function>("original_open")?;
 
-        Ok(std_io_open_func.call((file_name, mode)).unwrap())
-    }).unwrap();
+        std_io_open_func.call((file_name, mode))
+    })?;
 
-    std_io_table.set("open", working_dir_io).unwrap();
+    std_io_table.set("open", working_dir_io)?;
 
-    globals.set("ScriptAbsoluteWorkingDir", working_dir).unwrap();
+    globals.set("ScriptAbsoluteWorkingDir", working_dir)?;
 
-    lua.load(&fs::read_to_string(String::from(working_dir) + "Classes/GeneticSolverWorker.lua").unwrap())
-        .exec()
-        .unwrap();
+    let worker_script_path = String::from(working_dir) + "Classes/GeneticSolverWorker.lua";
 
-    let mut stored_session_number = 0;
+    let worker_script = fs::read_to_string(&worker_script_path)
+        .map_err(|err| LuaError::RuntimeError(format!("Cannot read {}: {}", worker_script_path, err)))?;
 
-    let lua_build: LuaTable = globals.get("build").unwrap();
+    lua.load(&worker_script).exec()?;
 
-    let calculate_stats_func = globals.get::<&str, Function>("GeneticWorkerCalculateStats").unwrap();
-    let init_session_func = globals.get::<&str, Function>("GeneticWorkerInitializeSession").unwrap();
-
-    let mut session_process_runtime: Option<Box<SessionProcessRuntime>> = None;
+    Ok(WorkerRuntime {
+        lua_build: globals.get("build")?,
+        calculate_stats_func: globals.get::<&str, Function>("GeneticWorkerCalculateStats")?,
+        init_session_func: globals.get::<&str, Function>("GeneticWorkerInitializeSession")?,
+        stored_session_number: 0,
+        session_process_runtime: None
+    })
+}
 
-    loop {
-        let mut dna_command = reader_dna_queue_channel.recv().unwrap();
+fn evaluate_dna_command(worker_runtime: &mut WorkerRuntime, session: &RwLock<Session>, dna_command: &mut DnaCommand) -> LuaResult<()>
+{
+    {
+        let session = session.read().unwrap();
 
+        if session.number != worker_runtime.stored_session_number
         {
-            let session = session.read().unwrap();
+            worker_runtime.session_process_runtime = None;
 
-            if session.number != stored_session_number
-            {
-                let _: LuaValue = init_session_func.call(()).unwrap();
+            let _: LuaValue = worker_runtime.init_session_func.call(())?;
+
+            let dna_encoder = create_dna_encoder(&worker_runtime.lua_build)?;
+
+            let fitness_function_calculator =
+                FitnessFunctionCalculator::new(
+                    session.targets.clone(),
+                    session.fitness_aggregation
+                );
 
-                stored_session_number = session.number;
+            worker_runtime.session_process_runtime = Some(
+                SessionProcessRuntime {
+                    target_normal_nodes_count: session.target_normal_nodes_count,
+                    target_ascendancy_nodes_count: session.target_ascendancy_nodes_count,
+                    dna_encoder,
+                    fitness_function_calculator,
+                    fitness_cache: session.fitness_cache.clone(),
+                    stat_cache: session.stat_cache.clone()
+                }
+            );
+
+            worker_runtime.stored_session_number = session.number;
+        }
+    };
+
+    let session_process_runtime = worker_runtime.session_process_runtime
+        .as_mut()
+        .ok_or_else(|| LuaError::RuntimeError(String::from("Session is not initialized")))?;
+
+    let dna = dna_command.dna
+        .as_mut()
+        .ok_or_else(|| LuaError::RuntimeError(String::from("Dna command is empty")))?;
+
+    // Caches are checked before build is changed, so hits do not run PoB
+    let dna_convert_result =
+        session_process_runtime.dna_encoder.decode_dna(
+            dna,
+            session_process_runtime.target_normal_nodes_count,
+            session_process_runtime.target_ascendancy_nodes_count)?;
+
+    let cache_key = DnaCacheFitness::get_key(&dna_convert_result);
+
+    dna.unreachable_node_indexes = dna_convert_result.unreachable_node_indexes.clone();
+    dna.decoded_tree_key = Some(cache_key);
+    dna.allocated_node_ids = dna_convert_result.allocated_node_ids.clone();
+
+    if session_process_runtime.fitness_cache.try_apply_fitness_scores(cache_key, dna)
+    {
+        return Ok(());
+    }
+
+    let stat_cache_key = StatCache::get_key(&dna_convert_result);
+
+    if let Some(stat_cache) = &session_process_runtime.stat_cache
+    {
+        if let Some(cached_stat_values) = stat_cache.get_stats(stat_cache_key)
+        {
+            let mut stats = FitnessFunctionCalculatorStats::from_cached_stats(cached_stat_values);
 
-                let target_normal_nodes_count = session.target_normal_nodes_count;
-                let target_ascendancy_nodes_count = session.target_ascendancy_nodes_count;
+            calculate_targets_for_dna(&session_process_runtime.fitness_function_calculator,
+                                      &mut stats,
+                                      dna)?;
 
-                let dna_encoder = create_dna_encoder(&lua_build);
+            if !stats.is_missing_stats()
+            {
+                stat_cache.add_hit();
 
-                let fitness_function_calculator =
-                    FitnessFunctionCalculator::new(
-                        session.targets.clone()
-                    );
+                session_process_runtime.fitness_cache.set_fitness_scores(cache_key, dna);
 
-                session_process_runtime = Some(
-                    Box::new(SessionProcessRuntime {
-                        target_normal_nodes_count,
-                        target_ascendancy_nodes_count,
-                        dna_encoder,
-                        fitness_function_calculator,
-                    })
-                );
+                return Ok(());
             }
-        };
+        }
+    }
 
-        let mut iteration_session_runtime = session_process_runtime.take().unwrap();
+    session_process_runtime.dna_encoder.apply_to_build(&worker_runtime.lua_build, &dna_convert_result)?;
 
-        let _dna_convert_result =
-            iteration_session_runtime.dna_encoder.convert_dna_to_build(
-                &lua_build,
-                dna_command.dna.as_ref().unwrap(),
-                iteration_session_runtime.target_normal_nodes_count,
-                iteration_session_runtime.target_ascendancy_nodes_count);
+    let stats_env: LuaTable = worker_runtime.calculate_stats_func.call(())?;
 
-        let stats_env: LuaTable = calculate_stats_func.call(()).unwrap();
+    let mut stats = FitnessFunctionCalculatorStats::new(&stats_env);
 
-        let mut stats = FitnessFunctionCalculatorStats::new(&stats_env);
+    calculate_targets_for_dna(&session_process_runtime.fitness_function_calculator,
+                              &mut stats,
+                              dna)?;
 
-        calculate_targets_for_dna(&iteration_session_runtime.fitness_function_calculator,
-                                  &mut stats,
-                                  dna_command.dna.as_mut().unwrap());
+    if let Some(stat_cache) = &session_process_runtime.stat_cache
+    {
+        stat_cache.merge_stats(stat_cache_key, stats.get_stat_values());
+    }
 
-        writer_dna_result_queue_channel.send(dna_command).unwrap();
+    session_process_runtime.fitness_cache.set_fitness_scores(cache_key, dna);
 
-        session_process_runtime = Some(iteration_session_runtime);
+    Ok(())
+}
+
+pub(crate) fn panic_message(panic_payload: &(dyn Any + Send)) -> String
+{
+    match panic_payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match panic_payload.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => String::from("unknown panic")
+        }
     }
 }
 
-fn calculate_targets_for_dna(fitness_function_calculator: &FitnessFunctionCalculator, stats: &mut FitnessFunctionCalculatorStats, dna: &mut Dna)
+fn calculate_targets_for_dna(fitness_function_calculator: &FitnessFunctionCalculator, stats: &mut FitnessFunctionCalculatorStats, dna: &mut Dna) -> LuaResult<()>
 {
     for (index_target, target) in fitness_function_calculator.targets.iter().enumerate()
     {
-        dna.fitness_score_targets[index_target] = target.calc_fitness_score(&fitness_function_calculator, stats);
+        dna.fitness_score_targets[index_target] = target.calc_fitness_score(fitness_function_calculator, stats)?;
     }
 
-    dna.fitness_score = fitness_function_calculator.calculate_and_get_fitness_score(stats);
+    dna.fitness_score = fitness_function_calculator.aggregate_fitness_score(&dna.fitness_score_targets);
+
+    Ok(())
 }